@@ -0,0 +1,166 @@
+// Enums
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FieldState {
+    Start,
+    Unquoted,
+    Quoted,
+    Closed,
+}
+
+// Structs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+// Functions
+pub fn write_record<I, S>(out: &mut String, fields: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        let field = field.as_ref();
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+pub fn parse_records(data: &str) -> Result<Vec<(usize, Vec<String>)>, ImportError> {
+    let mut records = Vec::new();
+    let mut chars = data.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut state = FieldState::Start;
+
+        loop {
+            let c = chars.next();
+            match (state, c) {
+                (FieldState::Quoted, None) => {
+                    return Err(ImportError {
+                        line: start,
+                        reason: "unterminated quoted field",
+                    });
+                }
+                (FieldState::Quoted, Some('"')) => {
+                    if chars.next_if_eq(&'"').is_some() {
+                        field.push('"');
+                    } else {
+                        state = FieldState::Closed;
+                    }
+                }
+                (FieldState::Quoted, Some(c)) => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+                (FieldState::Start, Some('"')) => state = FieldState::Quoted,
+                (_, Some('"')) => {
+                    return Err(ImportError {
+                        line,
+                        reason: "unexpected quote",
+                    });
+                }
+                (_, Some(',')) => {
+                    fields.push(std::mem::take(&mut field));
+                    state = FieldState::Start;
+                }
+                (_, Some('\r')) if chars.peek() == Some(&'\n') => {}
+                (_, None | Some('\n')) => {
+                    line += 1;
+                    fields.push(std::mem::take(&mut field));
+                    break;
+                }
+                (FieldState::Closed, Some(_)) => {
+                    return Err(ImportError {
+                        line,
+                        reason: "unexpected text after quoted field",
+                    });
+                }
+                (_, Some(c)) => {
+                    field.push(c);
+                    state = FieldState::Unquoted;
+                }
+            }
+        }
+
+        if fields != [""] {
+            records.push((start, fields));
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_round_trip() {
+        let fields = ["plain", "with, comma", "with \"quotes\"", "two\nlines", ""];
+        let mut out = String::new();
+        write_record(&mut out, fields);
+        write_record(&mut out, ["next"]);
+
+        assert_eq!(
+            Ok(vec![
+                (1, fields.iter().map(|f| f.to_string()).collect()),
+                (3, vec!["next".to_string()]),
+            ]),
+            parse_records(&out)
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_blank_lines() {
+        assert_eq!(
+            Ok(vec![
+                (1, vec!["a".to_string(), "b".to_string()]),
+                (3, vec!["c".to_string()]),
+            ]),
+            parse_records("a,b\n\nc")
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_malformed_lines() {
+        assert_eq!(
+            Err(ImportError {
+                line: 2,
+                reason: "unterminated quoted field"
+            }),
+            parse_records("a,b\n\"open,c\n")
+        );
+        assert_eq!(
+            Err(ImportError {
+                line: 3,
+                reason: "unexpected quote"
+            }),
+            parse_records("a\nb\nc\"d\n")
+        );
+        assert_eq!(
+            Err(ImportError {
+                line: 1,
+                reason: "unexpected text after quoted field"
+            }),
+            parse_records("\"a\"b,c")
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+// Consts
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_MAX_IDEMPOTENCY_KEYS: usize = 1024;
+
+// Structs
+#[derive(Debug)]
+pub struct IdempotencyKeys {
+    ttl: Duration,
+    max_keys: usize,
+    keys: HashMap<String, (Instant, Uuid)>,
+}
+
+// Impls
+impl IdempotencyKeys {
+    pub fn new(ttl: Duration, max_keys: usize) -> Self {
+        assert!(max_keys > 0, "at least one idempotency key must be kept");
+
+        Self {
+            ttl,
+            max_keys,
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &str, now: Instant) -> Option<Uuid> {
+        self.purge(now);
+        self.keys.get(key).map(|(_, id)| *id)
+    }
+
+    pub fn insert(&mut self, key: String, id: Uuid, now: Instant) {
+        self.purge(now);
+        if self.keys.len() >= self.max_keys && !self.keys.contains_key(&key) {
+            let oldest = self
+                .keys
+                .iter()
+                .min_by_key(|(_, (seen_at, _))| *seen_at)
+                .map(|(key, _)| key.clone())
+                .expect("a full map has an oldest key");
+            self.keys.remove(&oldest);
+        }
+
+        self.keys.insert(key, (now, id));
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn purge(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.keys
+            .retain(|_, (seen_at, _)| now.duration_since(*seen_at) < ttl);
+    }
+}
+
+impl Default for IdempotencyKeys {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDEMPOTENCY_KEY_TTL, DEFAULT_MAX_IDEMPOTENCY_KEYS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_expire_after_ttl() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 8);
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        keys.insert("retry-1".into(), id, now);
+
+        assert_eq!(Some(id), keys.get("retry-1", now + Duration::from_secs(59)));
+        assert_eq!(None, keys.get("retry-1", now + Duration::from_secs(60)));
+        assert!(keys.is_empty());
+    }
+
+    #[test]
+    fn test_oldest_key_is_evicted_when_full() {
+        let mut keys = IdempotencyKeys::new(Duration::from_secs(60), 2);
+        let now = Instant::now();
+        let ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+
+        keys.insert("a".into(), ids[0], now);
+        keys.insert("b".into(), ids[1], now + Duration::from_secs(1));
+        keys.insert("c".into(), ids[2], now + Duration::from_secs(2));
+
+        assert_eq!(2, keys.len());
+        assert_eq!(None, keys.get("a", now + Duration::from_secs(2)));
+        assert_eq!(Some(ids[1]), keys.get("b", now + Duration::from_secs(2)));
+        assert_eq!(Some(ids[2]), keys.get("c", now + Duration::from_secs(2)));
+    }
+}
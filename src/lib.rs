@@ -1,39 +1,141 @@
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    future::Future,
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
+use arc_swap::ArcSwap;
+use askama::Template;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::get,
-    Form, Router,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::{from_fn, from_fn_with_state},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    routing::{delete, get, patch, post, put},
+    Extension, Form, Json, Router,
+};
+use csv::ImportError;
+use futures_util::{stream, Stream};
+use idempotency::{IdempotencyKeys, IDEMPOTENCY_KEY_HEADER};
+use link::{CompletionLinkError, CompletionLinks};
+use middleware::{
+    format_json, limit_mutations, negotiate_errors, persist_mutations, propagate_request_id,
+    reject_until_ready, ErrorMessage, MutationLimit, RequestId, REQUEST_ID_HEADER,
+};
+use models::{normalize_tags, Priority, Todo, TodoEvent, TodoListFilter, TodoToggleAction};
+use quick_add::parse_quick_add;
+use repository::{
+    list_order, paginate, parse_timestamp, Cursor, LintWarning, TodoPatch, TodoQuery, TodoRepo,
+    TodoRepoError, TodoStats, DEFAULT_MAX_TEXT_LEN,
+};
+use serde::{Deserialize, Serialize};
+use share::{decode_share_code, encode_share_code, ShareCodeError};
+use store::{FileTodoStore, TodoStore};
+use tokio::{
+    net::TcpListener,
+    sync::{
+        broadcast::{self, error::RecvError},
+        RwLock,
+    },
+    task::JoinHandle,
+};
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    limit::RequestBodyLimitLayer,
+    services::ServeDir,
+    trace::TraceLayer,
 };
-use models::{Todo, TodoListFilter, TodoToggleAction};
-use repository::{TodoRepo, TodoRepoError};
-use tokio::sync::RwLock;
-use tower_http::{services::ServeDir, trace::TraceLayer};
 use uuid::Uuid;
 
+pub mod csv;
+pub mod idempotency;
+pub mod link;
+pub mod middleware;
 pub mod models;
+pub mod quick_add;
 pub mod repository;
+pub mod share;
+pub mod store;
 
 // Types
 pub type SharedState = Arc<RwLock<AppState>>;
+pub type SharedSnapshot = Arc<ArcSwap<SnapshotView>>;
 
 // Enums
 pub enum AppError {
     TodoRepo(TodoRepoError),
+    ShareCode(ShareCodeError),
+    CompletionLink(CompletionLinkError),
+    CsvImport(ImportError),
+    DeleteDisabled,
+    ConfirmationRequired,
+    TooManyMutations,
+    NotReady,
 }
 
 // struct
 #[derive(Debug)]
 pub struct AppState {
-    pub selected_filter: TodoListFilter,
+    pub selected_filter: SelectedFilter,
     pub toggle_action: TodoToggleAction,
     pub todo_repo: TodoRepo,
+    pub mutation_limit: Arc<MutationLimit>,
+    pub unique_text: bool,
+    pub keep_text_on_blank_update: bool,
+    pub reset_filter_on_delete_completed: bool,
+    pub snapshot_reads: Option<SharedSnapshot>,
+    pub completion_links: CompletionLinks,
+    pub allow_delete: bool,
+    pub store: Option<Box<dyn TodoStore>>,
+    pub focus_min_priority: Priority,
+    pub pretty_json: bool,
+    pub max_text_len: usize,
+    pub tag_allowlist: Option<HashSet<String>>,
+    pub is_ready: bool,
+    pub events: broadcast::Sender<TodoEvent>,
+    pub cors_origins: Vec<String>,
+    pub max_body_bytes: usize,
+    pub idempotency_keys: IdempotencyKeys,
+}
+
+pub struct AppStateBuilder {
+    state: AppState,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+/// The filter the UI last listed with. Shared with the router so list
+/// requests can record it without taking the state lock.
+#[derive(Debug, Clone)]
+pub struct SelectedFilter(Arc<ArcSwap<TodoListFilter>>);
+
+#[derive(Debug, Clone)]
+pub struct SnapshotView {
+    pub num_completed_items: u32,
+    pub num_active_items: u32,
+    pub num_all_items: u32,
+    pub toggle_action: TodoToggleAction,
+    pub allow_delete: bool,
+    pub items: Vec<Todo>,
 }
 
+#[derive(Template)]
+#[template(path = "responses/index.html")]
 struct GetIndexResponse;
 
+#[derive(Template)]
+#[template(path = "responses/list_todos.html")]
 struct ListTodosResponse {
     num_completed_items: u32,
     num_active_items: u32,
@@ -44,10 +146,27 @@ struct ListTodosResponse {
     items: Vec<Todo>,
 }
 
+#[derive(Deserialize)]
 struct ListTodosQuery {
     pub filter: TodoListFilter,
+    pub regex: Option<String>,
+    pub q: Option<String>,
+    pub priority: Option<Priority>,
+    pub tag: Option<String>,
+    pub due_after: Option<u64>,
+    pub due_before: Option<u64>,
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    #[serde(default)]
+    pub focus: bool,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "default_page_limit")]
+    pub limit: usize,
 }
 
+#[derive(Template)]
+#[template(path = "responses/toggle_completed_todos.html")]
 struct ToggleCompletedTodosResponse {
     num_completed_items: u32,
     num_active_items: u32,
@@ -58,10 +177,15 @@ struct ToggleCompletedTodosResponse {
     items: Vec<Todo>,
 }
 
+#[derive(Deserialize)]
 struct ToggleCompletedTodosQuery {
     action: TodoToggleAction,
+    #[serde(default = "default_scoped")]
+    scoped: bool,
 }
 
+#[derive(Template)]
+#[template(path = "responses/delete_completed_todos.html")]
 struct DeletedCompletedTodosResponse {
     num_completed_items: u32,
     num_active_items: u32,
@@ -72,10 +196,42 @@ struct DeletedCompletedTodosResponse {
     items: Vec<Todo>,
 }
 
+#[derive(Serialize)]
+struct TodoStatsResponse {
+    #[serde(flatten)]
+    totals: TodoStats,
+    age_distribution: Vec<(&'static str, u32)>,
+    completions_by_hour: [u32; 24],
+}
+
+#[derive(Deserialize)]
+struct TodoStatsQuery {
+    #[serde(default)]
+    tz_offset: i32,
+}
+
+#[derive(Deserialize)]
+struct BurndownQuery {
+    #[serde(default = "default_burndown_days")]
+    days: u32,
+    #[serde(default)]
+    tz_offset: i32,
+}
+
+#[derive(Template)]
+#[template(path = "responses/get_todo.html")]
+struct GetTodoResponse {
+    item: Todo,
+}
+
+#[derive(Template)]
+#[template(path = "responses/edit_todo.html")]
 struct EditTodoResponse {
     item: Todo,
 }
 
+#[derive(Template)]
+#[template(path = "responses/update_todo.html")]
 struct UpdateTodoResponse {
     num_completed_items: u32,
     num_active_items: u32,
@@ -86,11 +242,19 @@ struct UpdateTodoResponse {
     item: Option<Todo>,
 }
 
+#[derive(Deserialize)]
 struct UpdateTodoForm {
     is_completed: Option<bool>,
     text: Option<String>,
+    progress: Option<u8>,
+    priority: Option<Priority>,
+    due_at: Option<u64>,
+    tags: Option<String>,
+    version: Option<u64>,
 }
 
+#[derive(Template)]
+#[template(path = "responses/delete_todo.html")]
 struct DeleteTodoResponse {
     num_completed_items: u32,
     num_active_items: u32,
@@ -100,6 +264,8 @@ struct DeleteTodoResponse {
     action: TodoToggleAction,
 }
 
+#[derive(Template)]
+#[template(path = "responses/create_todo.html")]
 struct CreateTodoResponse {
     num_completed_items: u32,
     num_active_items: u32,
@@ -110,17 +276,511 @@ struct CreateTodoResponse {
     item: Option<Todo>,
 }
 
+#[derive(Deserialize)]
 struct CreateTodoForm {
     text: String,
+    #[serde(default)]
+    is_draft: bool,
+    priority: Option<Priority>,
+    due_at: Option<u64>,
+    tags: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClearTodosQuery {
+    #[serde(default)]
+    confirm: bool,
+}
+
+#[derive(Deserialize)]
+struct CreateTodoQuery {
+    #[serde(default)]
+    dedupe: bool,
+}
+
+#[derive(Deserialize)]
+struct ReorderTodoForm {
+    position: usize,
+}
+
+#[derive(Deserialize)]
+struct QuickAddForm {
+    input: String,
+}
+
+#[derive(Deserialize)]
+struct SetTodoMetadataForm {
+    value: String,
+}
+
+#[derive(Serialize)]
+struct ApiListTodosResponse {
+    num_completed_items: u32,
+    num_active_items: u32,
+    num_all_items: u32,
+    items: Vec<Todo>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BulkClearTagsRequest {
+    ids: Vec<Uuid>,
+}
+
+#[derive(Serialize)]
+struct BulkClearTagsResponse {
+    missing: Vec<Uuid>,
+}
+
+#[derive(Serialize)]
+struct ImportCsvResponse {
+    imported: usize,
+    num_completed_items: u32,
+    num_active_items: u32,
+    num_all_items: u32,
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    todos: u32,
+}
+
+#[derive(Serialize)]
+struct ReadyResponse {
+    status: &'static str,
+}
+
+#[derive(Deserialize)]
+struct BulkCreateTodoItem {
+    text: String,
+}
+
+#[derive(Serialize)]
+struct BulkCreateTodosResponse {
+    num_completed_items: u32,
+    num_active_items: u32,
+    num_all_items: u32,
+    items: Vec<Todo>,
+}
+
+#[derive(Deserialize)]
+struct ApiListTodosQuery {
+    filter: Option<TodoListFilter>,
+    after: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct CompleteTodoQuery {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct ImportShareCodeForm {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct SetTodoExpiryForm {
+    expires_at: u64,
+}
+
+#[derive(Deserialize)]
+struct SetTodoDueForm {
+    due_at: u64,
+}
+
+// Consts
+const MAX_BURNDOWN_DAYS: u32 = 366;
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const MAX_PAGE_LIMIT: usize = 500;
+pub const NUM_CHANGED_HEADER: &str = "x-num-changed";
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+pub const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+// Macros
+macro_rules! impl_template_response {
+    ($($response:ty),* $(,)?) => {
+        $(
+            impl IntoResponse for $response {
+                fn into_response(self) -> Response {
+                    match self.render() {
+                        Ok(html) => Html(html).into_response(),
+                        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                    }
+                }
+            }
+        )*
+    };
 }
 
 // Impls
+impl_template_response!(
+    GetIndexResponse,
+    ListTodosResponse,
+    ToggleCompletedTodosResponse,
+    DeletedCompletedTodosResponse,
+    GetTodoResponse,
+    EditTodoResponse,
+    UpdateTodoResponse,
+    DeleteTodoResponse,
+    CreateTodoResponse,
+);
+
+impl AppStateBuilder {
+    pub fn new() -> Self {
+        Self {
+            state: AppState {
+                selected_filter: SelectedFilter::new(TodoListFilter::All),
+                toggle_action: TodoToggleAction::Check,
+                todo_repo: TodoRepo::default(),
+                mutation_limit: Arc::new(MutationLimit::default()),
+                unique_text: false,
+                keep_text_on_blank_update: true,
+                reset_filter_on_delete_completed: false,
+                snapshot_reads: None,
+                completion_links: CompletionLinks::default(),
+                allow_delete: true,
+                store: None,
+                focus_min_priority: Priority::Medium,
+                pretty_json: false,
+                max_text_len: DEFAULT_MAX_TEXT_LEN,
+                tag_allowlist: None,
+                is_ready: true,
+                events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+                cors_origins: Vec::new(),
+                max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+                idempotency_keys: IdempotencyKeys::default(),
+            },
+        }
+    }
+
+    pub fn filter(self, filter: TodoListFilter) -> Self {
+        self.state.selected_filter.set(filter);
+        self
+    }
+
+    pub fn toggle_action(mut self, toggle_action: TodoToggleAction) -> Self {
+        self.state.toggle_action = toggle_action;
+        self
+    }
+
+    pub fn todo_repo(mut self, todo_repo: TodoRepo) -> Self {
+        self.state.todo_repo = todo_repo;
+        self
+    }
+
+    pub fn mutation_limit(mut self, mutation_limit: Arc<MutationLimit>) -> Self {
+        self.state.mutation_limit = mutation_limit;
+        self
+    }
+
+    pub fn unique_text(mut self, unique_text: bool) -> Self {
+        self.state.unique_text = unique_text;
+        self
+    }
+
+    pub fn keep_text_on_blank_update(mut self, keep_text_on_blank_update: bool) -> Self {
+        self.state.keep_text_on_blank_update = keep_text_on_blank_update;
+        self
+    }
+
+    pub fn reset_filter_on_delete_completed(
+        mut self,
+        reset_filter_on_delete_completed: bool,
+    ) -> Self {
+        self.state.reset_filter_on_delete_completed = reset_filter_on_delete_completed;
+        self
+    }
+
+    pub fn snapshot_reads(mut self, snapshot_reads: Option<SharedSnapshot>) -> Self {
+        self.state.snapshot_reads = snapshot_reads;
+        self
+    }
+
+    pub fn allow_delete(mut self, allow_delete: bool) -> Self {
+        self.state.allow_delete = allow_delete;
+        self
+    }
+
+    pub fn focus_min_priority(mut self, focus_min_priority: Priority) -> Self {
+        self.state.focus_min_priority = focus_min_priority;
+        self
+    }
+
+    pub fn pretty_json(mut self, pretty_json: bool) -> Self {
+        self.state.pretty_json = pretty_json;
+        self
+    }
+
+    pub fn max_text_len(mut self, max_text_len: usize) -> Self {
+        self.state.max_text_len = max_text_len;
+        self
+    }
+
+    pub fn max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.state.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    pub fn persistence_path(self, path: impl Into<PathBuf>) -> Self {
+        self.store(FileTodoStore::new(path))
+    }
+
+    pub fn store(mut self, store: impl TodoStore + 'static) -> Self {
+        self.state.store = Some(Box::new(store));
+        self
+    }
+
+    pub fn tag_allowlist<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.state.tag_allowlist = Some(normalize_tags(tags).into_iter().collect());
+        self
+    }
+
+    pub fn cors_origins<I, S>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.state.cors_origins = origins.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn build_state(self) -> AppState {
+        self.state
+    }
+
+    pub fn build(self) -> SharedState {
+        Arc::new(RwLock::new(self.state))
+    }
+}
+
+impl Default for AppStateBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Default for AppState {
+    fn default() -> Self {
+        AppStateBuilder::new().build_state()
+    }
+}
+
+impl AppState {
+    pub fn persist(&mut self) {
+        let Some(store) = &mut self.store else {
+            return;
+        };
+
+        if !self.is_ready {
+            tracing::warn!("skipping save to {store:?} before the initial load");
+            return;
+        }
+
+        if let Err(err) = store.persist(&mut self.todo_repo) {
+            tracing::error!("failed to save todos to {store:?}: {err}");
+        }
+    }
+
+    pub fn publish_snapshot(&self) {
+        if let Some(snapshot) = &self.snapshot_reads {
+            snapshot.store(Arc::new(SnapshotView::capture(self)));
+        }
+    }
+
+    pub fn notify(&self, event: TodoEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.events.send(event);
+    }
+
+    fn create_todo(
+        &mut self,
+        CreateTodoForm {
+            text,
+            is_draft,
+            priority,
+            due_at,
+            tags,
+        }: CreateTodoForm,
+        dedupe: bool,
+    ) -> Result<(Todo, bool), TodoRepoError> {
+        self.todo_repo.validate_text(&text, self.max_text_len)?;
+        let tags = tags.as_deref().map(parse_tags);
+        if let Some(tags) = &tags {
+            self.ensure_allowed_tags(tags)?;
+        }
+        // Drafts are never active, so there is nothing for them to dedupe against.
+        let dedupe = dedupe && !is_draft;
+        if self.unique_text && !(dedupe && self.todo_repo.find_active_by_text(&text).is_some()) {
+            self.todo_repo.ensure_unique_text(&text, None)?;
+        }
+
+        let item = if is_draft {
+            let item = self.todo_repo.create_draft(text);
+            self.todo_repo
+                .set_priority(&item.id, priority.unwrap_or_default())?
+        } else if dedupe {
+            match self.todo_repo.create_unique(text) {
+                (item, true) => match priority {
+                    Some(priority) => self.todo_repo.set_priority(&item.id, priority)?,
+                    None => item,
+                },
+                (existing, false) => return Ok((existing, false)),
+            }
+        } else {
+            self.todo_repo.create_with_priority(text, priority)
+        };
+        let item = match due_at {
+            Some(due_at) => self.todo_repo.set_due(&item.id, Some(unix_time(due_at)))?,
+            None => item,
+        };
+        let item = match tags {
+            Some(tags) => self.todo_repo.set_tags(&item.id, tags)?,
+            None => item,
+        };
+
+        self.toggle_action = TodoToggleAction::Check;
+        self.notify(TodoEvent::Created(item.id));
+        Ok((item, true))
+    }
+
+    fn replayed_todo(&mut self, key: Option<&str>) -> Option<Todo> {
+        let id = self.idempotency_keys.get(key?, Instant::now())?;
+        self.todo_repo.get(&id).ok()
+    }
+
+    fn remember_idempotency_key(&mut self, key: Option<String>, id: Uuid) {
+        if let Some(key) = key {
+            self.idempotency_keys.insert(key, id, Instant::now());
+        }
+    }
+
+    fn update_todo(
+        &mut self,
+        id: &Uuid,
+        todo_update: UpdateTodoForm,
+    ) -> Result<Todo, TodoRepoError> {
+        let text = todo_update
+            .text
+            .filter(|text| !(self.keep_text_on_blank_update && text.trim().is_empty()));
+        let tags = todo_update.tags.as_deref().map(parse_tags);
+        if let Some(tags) = &tags {
+            self.ensure_allowed_tags(tags)?;
+        }
+        self.todo_repo.ensure_version(id, todo_update.version)?;
+
+        if let Some(text) = &text {
+            self.todo_repo.validate_text(text, self.max_text_len)?;
+            if self.unique_text {
+                self.todo_repo.ensure_unique_text(text, Some(id))?;
+            }
+        }
+
+        let patch = TodoPatch {
+            text,
+            is_completed: todo_update.is_completed,
+            progress: todo_update.progress,
+            priority: todo_update.priority,
+            due_at: todo_update.due_at.map(unix_time),
+            tags,
+        };
+        let (item, next) = self.todo_repo.patch(id, patch, todo_update.version)?;
+
+        if todo_update.is_completed.is_some() || todo_update.progress.is_some() {
+            self.toggle_action =
+                if self.todo_repo.num_completed_items == self.todo_repo.num_all_items {
+                    TodoToggleAction::Uncheck
+                } else {
+                    TodoToggleAction::Check
+                };
+        }
+
+        self.notify(TodoEvent::Updated(item.id));
+        if let Some(next) = next {
+            self.notify(TodoEvent::Created(next.id));
+        }
+        Ok(item)
+    }
+
+    fn ensure_allowed_tags(&self, tags: &[String]) -> Result<(), TodoRepoError> {
+        let Some(allowlist) = &self.tag_allowlist else {
+            return Ok(());
+        };
+
+        let rejected: Vec<_> = normalize_tags(tags)
+            .into_iter()
+            .filter(|tag| !allowlist.contains(tag))
+            .collect();
+        if !rejected.is_empty() {
+            return Err(TodoRepoError::TagNotAllowed(rejected));
+        }
+
+        Ok(())
+    }
+
+    fn delete_todo(&mut self, id: &Uuid) -> Result<(), AppError> {
+        if !self.allow_delete {
+            return Err(AppError::DeleteDisabled);
+        }
+
+        self.todo_repo.delete(id)?;
+
+        self.toggle_action = if self.todo_repo.num_all_items == 0 {
+            TodoToggleAction::Check
+        } else {
+            TodoToggleAction::Uncheck
+        };
+        self.notify(TodoEvent::Deleted(*id));
+
+        Ok(())
+    }
+}
+
+impl SelectedFilter {
+    pub fn new(filter: TodoListFilter) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(filter)))
+    }
+
+    pub fn get(&self) -> TodoListFilter {
+        **self.0.load()
+    }
+
+    pub fn set(&self, filter: TodoListFilter) {
+        self.0.store(Arc::new(filter));
+    }
+}
+
+impl SnapshotView {
+    pub fn capture(state: &AppState) -> Self {
+        let mut items: Vec<_> = state.todo_repo.items.values().cloned().collect();
+        items.sort_by_key(list_order);
+
+        Self {
+            num_completed_items: state.todo_repo.num_completed_items,
+            num_active_items: state.todo_repo.num_active_items,
+            num_all_items: state.todo_repo.num_all_items,
+            toggle_action: state.toggle_action,
+            allow_delete: state.allow_delete,
+            items,
+        }
+    }
+}
+
+impl Default for SnapshotView {
     fn default() -> Self {
         Self {
-            selected_filter: TodoListFilter::All,
+            num_completed_items: 0,
+            num_active_items: 0,
+            num_all_items: 0,
             toggle_action: TodoToggleAction::Check,
-            todo_repo: TodoRepo::default(),
+            allow_delete: true,
+            items: Vec::new(),
         }
     }
 }
@@ -131,21 +791,192 @@ impl From<TodoRepoError> for AppError {
     }
 }
 
+impl From<ShareCodeError> for AppError {
+    fn from(value: ShareCodeError) -> Self {
+        Self::ShareCode(value)
+    }
+}
+
+impl From<ImportError> for AppError {
+    fn from(value: ImportError) -> Self {
+        Self::CsvImport(value)
+    }
+}
+
+impl From<CompletionLinkError> for AppError {
+    fn from(value: CompletionLinkError) -> Self {
+        Self::CompletionLink(value)
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            Self::TodoRepo(TodoRepoError::NotFound) => (StatusCode::NOT_FOUND, "Todo not found"),
+        let (status, error, message) = match &self {
+            Self::TodoRepo(TodoRepoError::NotFound) => {
+                (StatusCode::NOT_FOUND, "not_found", "Todo not found")
+            }
+            Self::TodoRepo(TodoRepoError::InvalidPattern) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_pattern",
+                "Invalid search pattern",
+            ),
+            Self::TodoRepo(TodoRepoError::Ambiguous) => (
+                StatusCode::CONFLICT,
+                "ambiguous_prefix",
+                "Todo id prefix is ambiguous",
+            ),
+            Self::TodoRepo(TodoRepoError::MetadataKeyNotFound) => (
+                StatusCode::NOT_FOUND,
+                "metadata_key_not_found",
+                "Metadata key not found",
+            ),
+            Self::TodoRepo(TodoRepoError::MetadataTooLarge) => (
+                StatusCode::BAD_REQUEST,
+                "metadata_too_large",
+                "Metadata exceeds the allowed size",
+            ),
+            Self::TodoRepo(TodoRepoError::DuplicateText) => (
+                StatusCode::CONFLICT,
+                "duplicate_text",
+                "A todo with this text already exists",
+            ),
+            Self::TodoRepo(TodoRepoError::DuplicateIds(_)) => (
+                StatusCode::CONFLICT,
+                "duplicate_ids",
+                "Imported todos have conflicting ids",
+            ),
+            Self::TodoRepo(TodoRepoError::InvalidProgress) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_progress",
+                "Progress must be between 0 and 100",
+            ),
+            Self::TodoRepo(TodoRepoError::InvalidInput) => (
+                StatusCode::BAD_REQUEST,
+                "blank_text",
+                "Todo text must not be blank",
+            ),
+            Self::TodoRepo(TodoRepoError::TextTooLong { .. }) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "text_too_long",
+                "Todo text is too long",
+            ),
+            Self::TodoRepo(TodoRepoError::InvalidTimestamp) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_timestamp",
+                "Timestamps must be RFC 3339",
+            ),
+            Self::TodoRepo(TodoRepoError::InvalidCursor) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_cursor",
+                "Invalid pagination cursor",
+            ),
+            Self::TodoRepo(TodoRepoError::TagNotAllowed(_)) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "tag_not_allowed",
+                "Tags are not allowed",
+            ),
+            Self::TodoRepo(TodoRepoError::VersionConflict { .. }) => (
+                StatusCode::CONFLICT,
+                "version_conflict",
+                "Todo was changed by someone else",
+            ),
+            Self::ShareCode(ShareCodeError::TooLarge) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "share_code_too_large",
+                "Share code is too large",
+            ),
+            Self::ShareCode(ShareCodeError::Malformed) => (
+                StatusCode::BAD_REQUEST,
+                "share_code_malformed",
+                "Share code is malformed",
+            ),
+            Self::CompletionLink(CompletionLinkError::Invalid) => (
+                StatusCode::FORBIDDEN,
+                "invalid_completion_link",
+                "Completion link is invalid",
+            ),
+            Self::CompletionLink(CompletionLinkError::Expired) => (
+                StatusCode::GONE,
+                "completion_link_expired",
+                "Completion link has expired",
+            ),
+            Self::CompletionLink(CompletionLinkError::AlreadyUsed) => (
+                StatusCode::GONE,
+                "completion_link_used",
+                "Completion link was already used",
+            ),
+            Self::CsvImport(_) => (StatusCode::BAD_REQUEST, "malformed_csv", "Malformed CSV"),
+            Self::DeleteDisabled => (
+                StatusCode::FORBIDDEN,
+                "delete_disabled",
+                "Deleting todos is disabled",
+            ),
+            Self::ConfirmationRequired => (
+                StatusCode::BAD_REQUEST,
+                "confirmation_required",
+                "Pass confirm=true to delete every todo",
+            ),
+            Self::TooManyMutations => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "too_many_mutations",
+                "Too many pending changes",
+            ),
+            Self::NotReady => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "not_ready",
+                "Todos are still loading",
+            ),
+        };
+
+        let message = match self {
+            Self::TodoRepo(TodoRepoError::TagNotAllowed(tags)) => {
+                format!("{message}: {}", tags.join(", "))
+            }
+            Self::CsvImport(ImportError { line, reason }) => {
+                format!("{message} on line {line}: {reason}")
+            }
+            Self::TodoRepo(TodoRepoError::VersionConflict { current }) => {
+                format!("{message} (current version is {current})")
+            }
+            _ => message.to_string(),
         };
 
-        (status, message).into_response()
+        let mut response = (
+            status,
+            Json(ErrorBody {
+                error,
+                message: message.clone(),
+            }),
+        )
+            .into_response();
+        response.extensions_mut().insert(ErrorMessage(message));
+
+        response
     }
 }
 
 // Fucntions
 pub fn app(shared_state: SharedState) -> Router {
-    Router::new()
+    let (mutation_limit, snapshot_reads, selected_filter, pretty_json, cors, max_body_bytes) = {
+        let state = shared_state
+            .try_read()
+            .expect("state must not be locked while building the router");
+
+        (
+            state.mutation_limit.clone(),
+            state.snapshot_reads.clone(),
+            state.selected_filter.clone(),
+            state.pretty_json,
+            cors_layer(&state.cors_origins),
+            state.max_body_bytes,
+        )
+    };
+
+    let router = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(get_index))
+        .route("/health", get(get_health))
+        .route("/ready", get(get_ready))
         .route(
             "/todo",
             get(list_todos)
@@ -154,80 +985,647 @@ pub fn app(shared_state: SharedState) -> Router {
                 .delete(delete_completed_todos),
         )
         .route(
-            "/todo/:id",
+            "/todo/{id}",
             get(edit_todo).patch(update_todo).delete(delete_todo),
         )
-        .layer(TraceLayer::new_for_http())
-        .with_state(shared_state)
+        .route("/todo/events", get(todo_events))
+        .route("/todo/all", delete(clear_todos))
+        .route("/todo/quick-add", post(quick_add_todo))
+        .route("/todo/bulk", post(bulk_create_todos))
+        .route("/todo/bulk/clear-tags", patch(bulk_clear_tags))
+        .route("/todo/stats", get(get_todo_stats))
+        .route("/todo/_lint", get(get_todo_lint))
+        .route("/todo/burndown", get(get_burndown))
+        .route("/todo/share-code", get(get_share_code))
+        .route("/todo/import-code", post(import_share_code))
+        .route("/todo/export.csv", get(export_csv))
+        .route("/todo/import.csv", post(import_csv))
+        .route("/todo/prefix/{prefix}", get(get_todo_by_prefix))
+        .route("/todo/ref/{ref_num}", get(get_todo_by_ref))
+        .route("/todo/tag/{tag}", get(list_todos_by_tag))
+        .route("/todo/{id}/publish", post(publish_todo))
+        .route("/todo/{id}/dismiss", post(dismiss_todo))
+        .route("/todo/{id}/restore", post(restore_todo))
+        .route("/todo/{id}/complete", get(complete_todo_by_link))
+        .route(
+            "/todo/{id}/meta/{key}",
+            get(get_todo_metadata)
+                .put(set_todo_metadata)
+                .delete(delete_todo_metadata),
+        )
+        .route(
+            "/todo/{id}/expiry",
+            put(set_todo_expiry).delete(clear_todo_expiry),
+        )
+        .route("/todo/{id}/due", put(set_todo_due).delete(clear_todo_due))
+        .route("/todo/{id}/order", patch(reorder_todo))
+        .route("/debug/size", get(get_repo_size))
+        .route("/api/todo", get(api_list_todos).post(api_create_todo))
+        .route(
+            "/api/todo/{id}",
+            get(api_get_todo)
+                .patch(api_update_todo)
+                .delete(api_delete_todo),
+        )
+        .layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .layer(Extension(snapshot_reads))
+        .layer(Extension(selected_filter))
+        .layer(from_fn_with_state(shared_state.clone(), persist_mutations))
+        .layer(from_fn_with_state(shared_state.clone(), reject_until_ready))
+        .layer(from_fn_with_state(mutation_limit, limit_mutations))
+        .layer(from_fn(negotiate_errors))
+        .layer(from_fn_with_state(pretty_json, format_json))
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request| {
+                let request_id = request
+                    .extensions()
+                    .get::<RequestId>()
+                    .map(|RequestId(id)| id.as_str())
+                    .unwrap_or_default();
+
+                tracing::info_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    request_id,
+                )
+            }),
+        )
+        .layer(from_fn(propagate_request_id));
+
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+    .with_state(shared_state)
 }
 
-async fn get_index() -> Result<GetIndexResponse, AppError> {
-    Ok(GetIndexResponse)
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| {
+            HeaderValue::from_str(origin)
+                .inspect_err(|_| tracing::warn!("ignoring invalid CORS origin {origin:?}"))
+                .ok()
+        }))
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::ACCEPT,
+                HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+                HeaderName::from_static(REQUEST_ID_HEADER),
+            ])
+            .expose_headers([
+                HeaderName::from_static(REQUEST_ID_HEADER),
+                HeaderName::from_static(NUM_CHANGED_HEADER),
+            ]),
+    )
 }
 
-async fn list_todos(
-    State(shared_state): State<SharedState>,
-    Query(ListTodosQuery { filter }): Query<ListTodosQuery>,
-) -> Result<ListTodosResponse, AppError> {
-    shared_state.write().await.selected_filter = filter;
-    let state = shared_state.read().await;
-    let items = state.todo_repo.list(&filter);
+fn default_burndown_days() -> u32 {
+    7
+}
 
-    Ok(ListTodosResponse {
-        num_completed_items: state.todo_repo.num_completed_items,
-        num_active_items: state.todo_repo.num_active_items,
-        num_all_items: state.todo_repo.num_all_items,
-        is_disabled_delete: state.todo_repo.num_completed_items == 0,
-        is_disabled_toggle: state.todo_repo.num_all_items == 0,
-        action: state.toggle_action,
-        items,
-    })
+fn default_scoped() -> bool {
+    true
 }
 
-async fn toggle_completed_todos(
-    State(shared_state): State<SharedState>,
-    Query(ToggleCompletedTodosQuery { action }): Query<ToggleCompletedTodosQuery>,
-) -> Result<ToggleCompletedTodosResponse, AppError> {
-    let mut state = shared_state.write().await;
-    state.toggle_action = match action {
-        TodoToggleAction::Uncheck => TodoToggleAction::Check,
-        TodoToggleAction::Check => TodoToggleAction::Uncheck,
-    };
+fn default_page_limit() -> usize {
+    DEFAULT_PAGE_LIMIT
+}
 
-    state.todo_repo.toggle_completed(&action);
-    let items = state.todo_repo.list(&state.selected_filter);
+fn unix_time(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
 
-    Ok(ToggleCompletedTodosResponse {
-        num_completed_items: state.todo_repo.num_completed_items,
-        num_active_items: state.todo_repo.num_active_items,
-        num_all_items: state.todo_repo.num_all_items,
-        is_disabled_delete: state.todo_repo.num_completed_items == 0,
-        is_disabled_toggle: state.todo_repo.num_all_items == 0,
-        action: state.toggle_action,
-        items,
-    })
+fn idempotency_key(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
 }
 
-async fn delete_completed_todos(
-    State(shared_state): State<SharedState>,
-) -> Result<DeletedCompletedTodosResponse, AppError> {
-    let mut state = shared_state.write().await;
+fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split(',').map(str::to_string).collect()
+}
+
+pub fn spawn_initial_load(shared_state: SharedState) -> JoinHandle<()> {
+    // The store sits out of the state while it loads; writes are rejected
+    // until it is back, so nothing tries to persist in the meantime.
+    let store = {
+        let mut state = shared_state
+            .try_write()
+            .expect("state must not be locked while starting the initial load");
+        state.is_ready = state.store.is_none();
+
+        state.store.take()
+    };
+
+    tokio::spawn(async move {
+        let Some(store) = store else {
+            return;
+        };
+
+        let (store, loaded) = tokio::task::spawn_blocking(move || {
+            let loaded = store.load();
+            (store, loaded)
+        })
+        .await
+        .expect("loading todos never panics");
+
+        let mut state = shared_state.write().await;
+        let todo_repo = match loaded {
+            Ok(todo_repo) => todo_repo,
+            Err(err) => {
+                tracing::error!("failed to load todos from {store:?}: {err}");
+                state.store = Some(store);
+                return;
+            }
+        };
+
+        state.store = Some(store);
+        state.todo_repo = TodoRepo {
+            truncate_timestamps: state.todo_repo.truncate_timestamps,
+            auto_title_len: state.todo_repo.auto_title_len,
+            max_completed_retained: state.todo_repo.max_completed_retained,
+            hard_delete_completed: state.todo_repo.hard_delete_completed,
+            ..todo_repo
+        };
+        state.is_ready = true;
+        state.publish_snapshot();
+    })
+}
+
+pub async fn run(listener: TcpListener, shared_state: SharedState) -> io::Result<()> {
+    run_until(listener, shared_state, shutdown_signal()).await
+}
+
+pub async fn run_until<F>(
+    listener: TcpListener,
+    shared_state: SharedState,
+    shutdown: F,
+) -> io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(listener, app(shared_state.clone()))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+
+    tracing::info!("shutting down, saving todos");
+    shared_state.write().await.persist();
+
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for ctrl-c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+pub fn spawn_expiry_sweeper(shared_state: SharedState, period: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let mut state = shared_state.write().await;
+            let removed = state.todo_repo.delete_expired(SystemTime::now());
+
+            if removed > 0 {
+                tracing::debug!("swept {removed} expired todos");
+                state.publish_snapshot();
+                state.notify(TodoEvent::Reset);
+            }
+        }
+    })
+}
+
+pub fn spawn_snapshot_publisher(shared_state: SharedState, period: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            shared_state.read().await.publish_snapshot();
+        }
+    })
+}
+
+async fn get_index() -> Result<GetIndexResponse, AppError> {
+    Ok(GetIndexResponse)
+}
+
+async fn get_health(State(shared_state): State<SharedState>) -> Json<HealthResponse> {
+    let todos = shared_state.read().await.todo_repo.num_all_items;
+
+    Json(HealthResponse {
+        status: "ok",
+        todos,
+    })
+}
+
+async fn get_ready(State(shared_state): State<SharedState>) -> (StatusCode, Json<ReadyResponse>) {
+    if shared_state.read().await.is_ready {
+        (StatusCode::OK, Json(ReadyResponse { status: "ready" }))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ReadyResponse { status: "loading" }),
+        )
+    }
+}
+
+async fn list_todos(
+    State(shared_state): State<SharedState>,
+    Extension(snapshot_reads): Extension<Option<SharedSnapshot>>,
+    Extension(selected_filter): Extension<SelectedFilter>,
+    Query(ListTodosQuery {
+        filter,
+        regex,
+        q,
+        priority,
+        tag,
+        due_after,
+        due_before,
+        created_after,
+        created_before,
+        focus,
+        offset,
+        limit,
+    }): Query<ListTodosQuery>,
+) -> Result<ListTodosResponse, AppError> {
+    let limit = limit.min(MAX_PAGE_LIMIT);
+    let query = TodoQuery {
+        text: q,
+        pattern: regex,
+        priority,
+        tag,
+        due_after: due_after.map(unix_time),
+        due_before: due_before.map(unix_time),
+        created_after: created_after.as_deref().map(parse_timestamp).transpose()?,
+        created_before: created_before.as_deref().map(parse_timestamp).transpose()?,
+        ..TodoQuery::new(filter)
+    };
+    selected_filter.set(filter);
+
+    if let Some(snapshot) = snapshot_reads
+        && query.is_plain()
+        && !focus
+        && filter != TodoListFilter::Manual
+    {
+        let now = SystemTime::now();
+        let view = snapshot.load();
+        return Ok(ListTodosResponse {
+            num_completed_items: view.num_completed_items,
+            num_active_items: view.num_active_items,
+            num_all_items: view.num_all_items,
+            is_disabled_delete: !view.allow_delete || view.num_completed_items == 0,
+            is_disabled_toggle: view.num_all_items == 0,
+            action: view.toggle_action,
+            items: view
+                .items
+                .iter()
+                .filter(|t| !t.is_expired(now) && filter.matches(t))
+                .skip(offset)
+                .take(limit)
+                .cloned()
+                .collect(),
+        });
+    }
+
+    let state = shared_state.read().await;
+    let items = if focus {
+        state.todo_repo.focus(state.focus_min_priority)
+    } else {
+        state.todo_repo.query(&query)?
+    };
+    let (items, _) = paginate(items, offset, limit);
+
+    Ok(ListTodosResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        items,
+    })
+}
+
+async fn todo_events(
+    State(shared_state): State<SharedState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = shared_state.read().await.events.subscribe();
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => TodoEvent::Reset,
+            Err(RecvError::Closed) => return None,
+        };
+        let event = Event::default()
+            .json_data(event)
+            .expect("todo events always serialize");
+
+        Some((Ok(event), receiver))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn list_todos_by_tag(
+    State(shared_state): State<SharedState>,
+    Path(tag): Path<String>,
+) -> ListTodosResponse {
+    let state = shared_state.read().await;
+
+    ListTodosResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        items: state.todo_repo.list_by_tag(&tag),
+    }
+}
+
+async fn reorder_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+    Form(ReorderTodoForm { position }): Form<ReorderTodoForm>,
+) -> Result<ListTodosResponse, AppError> {
+    let mut state = shared_state.write().await;
+    state.todo_repo.reorder(&id, position)?;
+    state.selected_filter.set(TodoListFilter::Manual);
+    state.notify(TodoEvent::Reset);
+
+    Ok(ListTodosResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        items: state.todo_repo.list(&TodoListFilter::Manual),
+    })
+}
+
+async fn toggle_completed_todos(
+    State(shared_state): State<SharedState>,
+    Query(ToggleCompletedTodosQuery { action, scoped }): Query<ToggleCompletedTodosQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    let filter = state.selected_filter.get();
+    let is_empty = if scoped {
+        state.todo_repo.list(&filter).is_empty()
+    } else {
+        state.todo_repo.num_all_items == 0
+    };
+
+    let (num_changed, recurrences) = if is_empty {
+        state.toggle_action = TodoToggleAction::Check;
+        (0, Vec::new())
+    } else {
+        state.toggle_action = match action {
+            TodoToggleAction::Uncheck => TodoToggleAction::Check,
+            TodoToggleAction::Check => TodoToggleAction::Uncheck,
+        };
+
+        if scoped {
+            state.todo_repo.toggle_completed_filtered(&action, &filter)
+        } else {
+            state.todo_repo.toggle_completed(&action)
+        }
+    };
+    for next in &recurrences {
+        state.notify(TodoEvent::Created(next.id));
+    }
+    if num_changed > 0 {
+        state.notify(TodoEvent::Reset);
+    }
+    let items = state.todo_repo.list(&filter);
+
+    Ok((
+        [(NUM_CHANGED_HEADER, num_changed.to_string())],
+        ToggleCompletedTodosResponse {
+            num_completed_items: state.todo_repo.num_completed_items,
+            num_active_items: state.todo_repo.num_active_items,
+            num_all_items: state.todo_repo.num_all_items,
+            is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+            is_disabled_toggle: state.todo_repo.num_all_items == 0,
+            action: state.toggle_action,
+            items,
+        },
+    ))
+}
+
+async fn delete_completed_todos(
+    State(shared_state): State<SharedState>,
+) -> Result<DeletedCompletedTodosResponse, AppError> {
+    let mut state = shared_state.write().await;
+    if !state.allow_delete {
+        return Err(AppError::DeleteDisabled);
+    }
 
     state.todo_repo.delete_completed();
     state.toggle_action = TodoToggleAction::Check;
-    let items = state.todo_repo.list(&state.selected_filter);
+    state.notify(TodoEvent::Reset);
+    if state.reset_filter_on_delete_completed {
+        state.selected_filter.set(TodoListFilter::All);
+    }
+
+    let items = state.todo_repo.list(&state.selected_filter.get());
+
+    Ok(DeletedCompletedTodosResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: true,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        items,
+    })
+}
+
+async fn clear_todos(
+    State(shared_state): State<SharedState>,
+    Query(ClearTodosQuery { confirm }): Query<ClearTodosQuery>,
+) -> Result<DeletedCompletedTodosResponse, AppError> {
+    let mut state = shared_state.write().await;
+    if !state.allow_delete {
+        return Err(AppError::DeleteDisabled);
+    }
+    if !confirm {
+        return Err(AppError::ConfirmationRequired);
+    }
+
+    state.todo_repo.clear();
+    state.toggle_action = TodoToggleAction::Check;
+    state.notify(TodoEvent::Reset);
 
     Ok(DeletedCompletedTodosResponse {
         num_completed_items: state.todo_repo.num_completed_items,
         num_active_items: state.todo_repo.num_active_items,
         num_all_items: state.todo_repo.num_all_items,
         is_disabled_delete: true,
+        is_disabled_toggle: true,
+        action: state.toggle_action,
+        items: Vec::new(),
+    })
+}
+
+async fn get_todo_stats(
+    State(shared_state): State<SharedState>,
+    Query(TodoStatsQuery { tz_offset }): Query<TodoStatsQuery>,
+) -> Result<Json<TodoStatsResponse>, AppError> {
+    let state = shared_state.read().await;
+    let now = SystemTime::now();
+
+    Ok(Json(TodoStatsResponse {
+        totals: state.todo_repo.stats(now),
+        age_distribution: state.todo_repo.age_distribution(now),
+        completions_by_hour: state.todo_repo.completions_by_hour(tz_offset),
+    }))
+}
+
+async fn get_todo_lint(
+    State(shared_state): State<SharedState>,
+) -> Result<Json<Vec<(Uuid, Vec<LintWarning>)>>, AppError> {
+    let warnings = shared_state.read().await.todo_repo.lint();
+
+    Ok(Json(warnings))
+}
+
+async fn get_burndown(
+    State(shared_state): State<SharedState>,
+    Query(BurndownQuery { days, tz_offset }): Query<BurndownQuery>,
+) -> Result<Json<Vec<(String, u32)>>, AppError> {
+    let state = shared_state.read().await;
+    let series =
+        state
+            .todo_repo
+            .burndown(SystemTime::now(), days.min(MAX_BURNDOWN_DAYS), tz_offset);
+
+    Ok(Json(series))
+}
+
+async fn get_share_code(State(shared_state): State<SharedState>) -> Result<String, AppError> {
+    let todos = shared_state.read().await.todo_repo.export();
+
+    Ok(encode_share_code(&todos))
+}
+
+async fn export_csv(State(shared_state): State<SharedState>) -> impl IntoResponse {
+    let csv = shared_state.read().await.todo_repo.export_csv();
+
+    ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], csv)
+}
+
+async fn import_csv(
+    State(shared_state): State<SharedState>,
+    body: String,
+) -> Result<Json<ImportCsvResponse>, AppError> {
+    let mut state = shared_state.write().await;
+    let imported = state.todo_repo.import_csv(&body)?;
+    state.notify(TodoEvent::Reset);
+    state.toggle_action = if state.todo_repo.num_completed_items == state.todo_repo.num_all_items
+        && state.todo_repo.num_all_items > 0
+    {
+        TodoToggleAction::Uncheck
+    } else {
+        TodoToggleAction::Check
+    };
+
+    Ok(Json(ImportCsvResponse {
+        imported,
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+    }))
+}
+
+async fn import_share_code(
+    State(shared_state): State<SharedState>,
+    Form(ImportShareCodeForm { code }): Form<ImportShareCodeForm>,
+) -> Result<ListTodosResponse, AppError> {
+    let todos = decode_share_code(&code)?;
+
+    let mut state = shared_state.write().await;
+    state.todo_repo.import(todos)?;
+    state.notify(TodoEvent::Reset);
+    state.toggle_action = if state.todo_repo.num_completed_items == state.todo_repo.num_all_items
+        && state.todo_repo.num_all_items > 0
+    {
+        TodoToggleAction::Uncheck
+    } else {
+        TodoToggleAction::Check
+    };
+    let items = state.todo_repo.list(&state.selected_filter.get());
+
+    Ok(ListTodosResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
         is_disabled_toggle: state.todo_repo.num_all_items == 0,
         action: state.toggle_action,
         items,
     })
 }
 
+async fn get_todo_by_prefix(
+    State(shared_state): State<SharedState>,
+    Path(prefix): Path<String>,
+) -> Result<GetTodoResponse, AppError> {
+    let item = shared_state.read().await.todo_repo.get_by_prefix(&prefix)?;
+
+    Ok(GetTodoResponse { item })
+}
+
+async fn get_todo_by_ref(
+    State(shared_state): State<SharedState>,
+    Path(ref_num): Path<u32>,
+) -> Result<GetTodoResponse, AppError> {
+    let item = shared_state.read().await.todo_repo.get_by_ref(ref_num)?;
+
+    Ok(GetTodoResponse { item })
+}
+
 async fn edit_todo(
     State(shared_state): State<SharedState>,
     Path(id): Path<Uuid>,
@@ -243,52 +1641,141 @@ async fn update_todo(
     Form(todo_update): Form<UpdateTodoForm>,
 ) -> Result<UpdateTodoResponse, AppError> {
     let mut state = shared_state.write().await;
-    let item = state
-        .todo_repo
-        .update(&id, todo_update.text, todo_update.is_completed)?;
+    let item = state.update_todo(&id, todo_update)?;
+    let item = state.selected_filter.get().matches(&item).then_some(item);
+
+    Ok(UpdateTodoResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        item,
+    })
+}
+
+async fn complete_todo_by_link(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+    Query(CompleteTodoQuery { token }): Query<CompleteTodoQuery>,
+) -> Result<GetTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    state.todo_repo.get(&id)?;
+    state
+        .completion_links
+        .redeem(&id, &token, SystemTime::now())?;
 
+    let (item, next) = state.todo_repo.complete_and_recur(&id)?;
+    state.notify(TodoEvent::Updated(id));
+    if let Some(next) = next {
+        state.notify(TodoEvent::Created(next.id));
+    }
     state.toggle_action = if state.todo_repo.num_completed_items == state.todo_repo.num_all_items {
         TodoToggleAction::Uncheck
     } else {
         TodoToggleAction::Check
     };
+    // GETs skip persist_mutations, so save and republish here.
+    state.persist();
+    state.publish_snapshot();
+
+    Ok(GetTodoResponse { item })
+}
+
+async fn dismiss_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<UpdateTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    let item = state.todo_repo.dismiss(&id)?;
+    state.notify(TodoEvent::Updated(id));
 
-    let item = match state.selected_filter {
-        TodoListFilter::Active if item.is_completed => None,
-        TodoListFilter::Active | TodoListFilter::All => Some(item),
-        TodoListFilter::Completed if item.is_completed => Some(item),
-        TodoListFilter::Completed => None,
+    state.toggle_action = if state.todo_repo.num_completed_items == state.todo_repo.num_all_items {
+        TodoToggleAction::Uncheck
+    } else {
+        TodoToggleAction::Check
     };
 
+    let item = state.selected_filter.get().matches(&item).then_some(item);
+
     Ok(UpdateTodoResponse {
         num_completed_items: state.todo_repo.num_completed_items,
         num_active_items: state.todo_repo.num_active_items,
         num_all_items: state.todo_repo.num_all_items,
-        is_disabled_delete: state.todo_repo.num_completed_items == 0,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
         is_disabled_toggle: state.todo_repo.num_all_items == 0,
         action: state.toggle_action,
         item,
     })
 }
 
-async fn delete_todo(
+async fn restore_todo(
     State(shared_state): State<SharedState>,
     Path(id): Path<Uuid>,
-) -> Result<DeleteTodoResponse, AppError> {
+) -> Result<UpdateTodoResponse, AppError> {
     let mut state = shared_state.write().await;
-    state.todo_repo.delete(&id)?;
+    let item = state.todo_repo.restore(&id)?;
+    state.notify(TodoEvent::Updated(id));
 
-    state.toggle_action = if state.todo_repo.num_all_items == 0 {
-        TodoToggleAction::Check
+    state.toggle_action = if state.todo_repo.num_completed_items == state.todo_repo.num_all_items {
+        TodoToggleAction::Uncheck
     } else {
+        TodoToggleAction::Check
+    };
+
+    let item = state.selected_filter.get().matches(&item).then_some(item);
+
+    Ok(UpdateTodoResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        item,
+    })
+}
+
+async fn publish_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<UpdateTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    let item = state.todo_repo.publish(&id)?;
+    state.notify(TodoEvent::Updated(id));
+
+    state.toggle_action = if state.todo_repo.num_completed_items == state.todo_repo.num_all_items {
         TodoToggleAction::Uncheck
+    } else {
+        TodoToggleAction::Check
     };
 
+    let item = state.selected_filter.get().matches(&item).then_some(item);
+
+    Ok(UpdateTodoResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+        is_disabled_toggle: state.todo_repo.num_all_items == 0,
+        action: state.toggle_action,
+        item,
+    })
+}
+
+async fn delete_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<DeleteTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    state.delete_todo(&id)?;
+
     Ok(DeleteTodoResponse {
         num_completed_items: state.todo_repo.num_completed_items,
         num_active_items: state.todo_repo.num_active_items,
         num_all_items: state.todo_repo.num_all_items,
-        is_disabled_delete: state.todo_repo.num_completed_items == 0,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
         is_disabled_toggle: state.todo_repo.num_all_items == 0,
         action: state.toggle_action,
     })
@@ -296,25 +1783,293 @@ async fn delete_todo(
 
 async fn create_todo(
     State(shared_state): State<SharedState>,
-    Form(CreateTodoForm { text }): Form<CreateTodoForm>,
-) -> Result<CreateTodoResponse, AppError> {
+    Query(CreateTodoQuery { dedupe }): Query<CreateTodoQuery>,
+    headers: HeaderMap,
+    Form(todo_create): Form<CreateTodoForm>,
+) -> Result<(StatusCode, CreateTodoResponse), AppError> {
+    let key = idempotency_key(&headers);
     let mut state = shared_state.write().await;
-    let item = state.todo_repo.create(&text);
-
-    let item = if state.selected_filter == TodoListFilter::Completed {
-        None
+    let (item, created) = match state.replayed_todo(key.as_deref()) {
+        Some(item) => (item, true),
+        None => {
+            let (item, created) = state.create_todo(todo_create, dedupe)?;
+            if created {
+                state.remember_idempotency_key(key, item.id);
+            }
+            (item, created)
+        }
+    };
+    let item = state.selected_filter.get().matches(&item).then_some(item);
+    // Plain creates keep answering 200; only dedupe tells a new todo apart.
+    let status = if dedupe && created {
+        StatusCode::CREATED
     } else {
-        Some(item)
+        StatusCode::OK
     };
 
-    state.toggle_action = TodoToggleAction::Check;
+    Ok((
+        status,
+        CreateTodoResponse {
+            num_completed_items: state.todo_repo.num_completed_items,
+            num_active_items: state.todo_repo.num_active_items,
+            num_all_items: state.todo_repo.num_all_items,
+            is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
+            is_disabled_toggle: state.todo_repo.num_all_items == 0,
+            action: state.toggle_action,
+            item,
+        },
+    ))
+}
+
+async fn quick_add_todo(
+    State(shared_state): State<SharedState>,
+    Form(QuickAddForm { input }): Form<QuickAddForm>,
+) -> Result<CreateTodoResponse, AppError> {
+    let quick_add = parse_quick_add(&input, SystemTime::now());
+    let todo_create = CreateTodoForm {
+        text: quick_add.text,
+        is_draft: false,
+        priority: quick_add.priority,
+        due_at: quick_add.due_at.map(|due_at| {
+            due_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        }),
+        tags: Some(quick_add.tags.join(",")),
+    };
+
+    let mut state = shared_state.write().await;
+    let (item, _) = state.create_todo(todo_create, false)?;
+    let item = state.selected_filter.get().matches(&item).then_some(item);
+
     Ok(CreateTodoResponse {
         num_completed_items: state.todo_repo.num_completed_items,
         num_active_items: state.todo_repo.num_active_items,
         num_all_items: state.todo_repo.num_all_items,
-        is_disabled_delete: state.todo_repo.num_completed_items == 0,
+        is_disabled_delete: !state.allow_delete || state.todo_repo.num_completed_items == 0,
         is_disabled_toggle: state.todo_repo.num_all_items == 0,
         action: state.toggle_action,
         item,
     })
 }
+
+async fn get_todo_metadata(
+    State(shared_state): State<SharedState>,
+    Path((id, key)): Path<(Uuid, String)>,
+) -> Result<String, AppError> {
+    let value = shared_state
+        .read()
+        .await
+        .todo_repo
+        .get_metadata(&id, &key)?;
+
+    Ok(value)
+}
+
+async fn set_todo_metadata(
+    State(shared_state): State<SharedState>,
+    Path((id, key)): Path<(Uuid, String)>,
+    Form(SetTodoMetadataForm { value }): Form<SetTodoMetadataForm>,
+) -> Result<String, AppError> {
+    let mut state = shared_state.write().await;
+    state.todo_repo.set_metadata(&id, key, value.clone())?;
+    state.notify(TodoEvent::Updated(id));
+
+    Ok(value)
+}
+
+async fn delete_todo_metadata(
+    State(shared_state): State<SharedState>,
+    Path((id, key)): Path<(Uuid, String)>,
+) -> Result<(), AppError> {
+    let mut state = shared_state.write().await;
+    state.todo_repo.delete_metadata(&id, &key)?;
+    state.notify(TodoEvent::Updated(id));
+
+    Ok(())
+}
+
+async fn set_todo_expiry(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+    Form(SetTodoExpiryForm { expires_at }): Form<SetTodoExpiryForm>,
+) -> Result<GetTodoResponse, AppError> {
+    let expires_at = unix_time(expires_at);
+    let mut state = shared_state.write().await;
+    let item = state.todo_repo.set_expiry(&id, Some(expires_at))?;
+    state.notify(TodoEvent::Updated(id));
+
+    Ok(GetTodoResponse { item })
+}
+
+async fn clear_todo_expiry(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<GetTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    let item = state.todo_repo.set_expiry(&id, None)?;
+    state.notify(TodoEvent::Updated(id));
+
+    Ok(GetTodoResponse { item })
+}
+
+async fn set_todo_due(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+    Form(SetTodoDueForm { due_at }): Form<SetTodoDueForm>,
+) -> Result<GetTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    let item = state.todo_repo.set_due(&id, Some(unix_time(due_at)))?;
+    state.notify(TodoEvent::Updated(id));
+
+    Ok(GetTodoResponse { item })
+}
+
+async fn clear_todo_due(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<GetTodoResponse, AppError> {
+    let mut state = shared_state.write().await;
+    let item = state.todo_repo.set_due(&id, None)?;
+    state.notify(TodoEvent::Updated(id));
+
+    Ok(GetTodoResponse { item })
+}
+
+async fn get_repo_size(State(shared_state): State<SharedState>) -> Result<String, AppError> {
+    let size = shared_state.read().await.todo_repo.approx_size_bytes();
+
+    Ok(size.to_string())
+}
+
+async fn bulk_create_todos(
+    State(shared_state): State<SharedState>,
+    Json(todos): Json<Vec<BulkCreateTodoItem>>,
+) -> Result<(StatusCode, Json<BulkCreateTodosResponse>), AppError> {
+    let mut state = shared_state.write().await;
+    let texts: Vec<_> = todos
+        .into_iter()
+        .map(|todo| todo.text)
+        .filter(|text| !text.trim().is_empty())
+        .collect();
+    for text in &texts {
+        state.todo_repo.validate_text(text, state.max_text_len)?;
+    }
+    if state.unique_text {
+        state.todo_repo.ensure_unique_texts(&texts)?;
+    }
+
+    let items = state.todo_repo.create_many(texts);
+    if !items.is_empty() {
+        state.toggle_action = TodoToggleAction::Check;
+    }
+    for item in &items {
+        state.notify(TodoEvent::Created(item.id));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(BulkCreateTodosResponse {
+            num_completed_items: state.todo_repo.num_completed_items,
+            num_active_items: state.todo_repo.num_active_items,
+            num_all_items: state.todo_repo.num_all_items,
+            items,
+        }),
+    ))
+}
+
+async fn bulk_clear_tags(
+    State(shared_state): State<SharedState>,
+    Json(BulkClearTagsRequest { ids }): Json<BulkClearTagsRequest>,
+) -> Json<BulkClearTagsResponse> {
+    let mut state = shared_state.write().await;
+    let missing = state.todo_repo.clear_tags(&ids);
+    for id in ids.iter().filter(|id| !missing.contains(id)) {
+        state.notify(TodoEvent::Updated(*id));
+    }
+
+    Json(BulkClearTagsResponse { missing })
+}
+
+async fn api_list_todos(
+    State(shared_state): State<SharedState>,
+    Query(ApiListTodosQuery {
+        filter,
+        after,
+        limit,
+    }): Query<ApiListTodosQuery>,
+) -> Result<Json<ApiListTodosResponse>, AppError> {
+    let state = shared_state.read().await;
+    let filter = filter.unwrap_or(TodoListFilter::All);
+    let (items, next_cursor) = if after.is_none() && limit.is_none() {
+        (state.todo_repo.list(&filter), None)
+    } else {
+        let after = after.map(|after| after.parse::<Cursor>()).transpose()?;
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        state.todo_repo.list_after(&filter, after, limit)
+    };
+
+    Ok(Json(ApiListTodosResponse {
+        num_completed_items: state.todo_repo.num_completed_items,
+        num_active_items: state.todo_repo.num_active_items,
+        num_all_items: state.todo_repo.num_all_items,
+        items,
+        next_cursor: next_cursor.map(|cursor| cursor.to_string()),
+    }))
+}
+
+async fn api_create_todo(
+    State(shared_state): State<SharedState>,
+    Query(CreateTodoQuery { dedupe }): Query<CreateTodoQuery>,
+    headers: HeaderMap,
+    Json(todo_create): Json<CreateTodoForm>,
+) -> Result<(StatusCode, Json<Todo>), AppError> {
+    let key = idempotency_key(&headers);
+    let mut state = shared_state.write().await;
+    let (item, created) = match state.replayed_todo(key.as_deref()) {
+        Some(item) => (item, true),
+        None => {
+            let (item, created) = state.create_todo(todo_create, dedupe)?;
+            if created {
+                state.remember_idempotency_key(key, item.id);
+            }
+            (item, created)
+        }
+    };
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+
+    Ok((status, Json(item)))
+}
+
+async fn api_get_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Todo>, AppError> {
+    let item = shared_state.read().await.todo_repo.get(&id)?;
+
+    Ok(Json(item))
+}
+
+async fn api_update_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+    Json(todo_update): Json<UpdateTodoForm>,
+) -> Result<Json<Todo>, AppError> {
+    let item = shared_state.write().await.update_todo(&id, todo_update)?;
+
+    Ok(Json(item))
+}
+
+async fn api_delete_todo(
+    State(shared_state): State<SharedState>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    shared_state.write().await.delete_todo(&id)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
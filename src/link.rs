@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+// Types
+type HmacSha256 = Hmac<Sha256>;
+
+// Enums
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompletionLinkError {
+    Invalid,
+    Expired,
+    AlreadyUsed,
+}
+
+// Structs
+pub struct CompletionLinks {
+    secret: Vec<u8>,
+    used: HashMap<(Uuid, u64), SystemTime>,
+}
+
+// Impls
+impl CompletionLinks {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+            used: HashMap::new(),
+        }
+    }
+
+    pub fn mint(&self, id: &Uuid, expires_at: SystemTime) -> String {
+        let expires = unix_secs(expires_at);
+        let signature = self.sign(id, expires).finalize().into_bytes();
+
+        format!("{expires}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    pub fn redeem(
+        &mut self,
+        id: &Uuid,
+        token: &str,
+        now: SystemTime,
+    ) -> Result<(), CompletionLinkError> {
+        let (raw_expires, signature) = token.split_once('.').ok_or(CompletionLinkError::Invalid)?;
+        let expires: u64 = raw_expires
+            .parse()
+            .map_err(|_| CompletionLinkError::Invalid)?;
+        // `parse` also takes "0123" and "+123"; only the minted spelling is valid.
+        if expires.to_string() != raw_expires {
+            return Err(CompletionLinkError::Invalid);
+        }
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| CompletionLinkError::Invalid)?;
+
+        self.sign(id, expires)
+            .verify_slice(&signature)
+            .map_err(|_| CompletionLinkError::Invalid)?;
+
+        let expires_at = SystemTime::UNIX_EPOCH + Duration::from_secs(expires);
+        if expires_at <= now {
+            return Err(CompletionLinkError::Expired);
+        }
+
+        self.used.retain(|_, expires_at| *expires_at > now);
+        if self.used.insert((*id, expires), expires_at).is_some() {
+            return Err(CompletionLinkError::AlreadyUsed);
+        }
+
+        Ok(())
+    }
+
+    fn sign(&self, id: &Uuid, expires: u64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key size");
+        mac.update(id.as_bytes());
+        mac.update(&expires.to_be_bytes());
+
+        mac
+    }
+}
+
+impl std::fmt::Debug for CompletionLinks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompletionLinks")
+            .field("used", &self.used.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for CompletionLinks {
+    fn default() -> Self {
+        let secret = [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat();
+
+        Self::new(secret)
+    }
+}
+
+// Functions
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn test_valid_token_is_redeemed_once() {
+        let mut links = CompletionLinks::new("secret");
+        let id = Uuid::new_v4();
+        let now = SystemTime::now();
+        let token = links.mint(&id, now + HOUR);
+
+        assert_eq!(Ok(()), links.redeem(&id, &token, now));
+        assert_eq!(
+            Err(CompletionLinkError::AlreadyUsed),
+            links.redeem(&id, &token, now)
+        );
+    }
+
+    #[test]
+    fn test_respelled_expiry_cannot_replay_token() {
+        let mut links = CompletionLinks::new("secret");
+        let id = Uuid::new_v4();
+        let now = SystemTime::now();
+        let token = links.mint(&id, now + HOUR);
+        let (expires, signature) = token.split_once('.').unwrap();
+
+        assert_eq!(Ok(()), links.redeem(&id, &token, now));
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            links.redeem(&id, &format!("0{expires}.{signature}"), now)
+        );
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            links.redeem(&id, &format!("+{expires}.{signature}"), now)
+        );
+    }
+
+    #[test]
+    fn test_respelled_expiry_is_rejected_before_first_use() {
+        let mut links = CompletionLinks::new("secret");
+        let id = Uuid::new_v4();
+        let now = SystemTime::now();
+        let token = links.mint(&id, now + HOUR);
+        let (expires, signature) = token.split_once('.').unwrap();
+
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            links.redeem(&id, &format!("+{expires}.{signature}"), now)
+        );
+        assert_eq!(Ok(()), links.redeem(&id, &token, now));
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let mut links = CompletionLinks::new("secret");
+        let id = Uuid::new_v4();
+        let now = SystemTime::now();
+        let token = links.mint(&id, now - HOUR);
+
+        assert_eq!(
+            Err(CompletionLinkError::Expired),
+            links.redeem(&id, &token, now)
+        );
+    }
+
+    #[test]
+    fn test_forged_tokens_are_rejected() {
+        let mut links = CompletionLinks::new("secret");
+        let id = Uuid::new_v4();
+        let now = SystemTime::now();
+        let token = links.mint(&id, now + HOUR);
+        let (_, signature) = token.split_once('.').unwrap();
+        let extended = format!("{}.{signature}", unix_secs(now + HOUR * 48));
+
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            links.redeem(&Uuid::new_v4(), &token, now)
+        );
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            links.redeem(&id, &extended, now)
+        );
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            CompletionLinks::new("other").redeem(&id, &token, now)
+        );
+        assert_eq!(
+            Err(CompletionLinkError::Invalid),
+            links.redeem(&id, "garbage", now)
+        );
+    }
+}
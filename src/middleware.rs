@@ -0,0 +1,226 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{AppError, SharedState};
+
+// Consts
+pub const DEFAULT_MAX_IN_FLIGHT_MUTATIONS: usize = 32;
+pub const DEFAULT_MAX_QUEUED_MUTATIONS: usize = 256;
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Structs
+#[derive(Debug)]
+pub struct MutationLimit {
+    permits: Semaphore,
+    max_in_flight: usize,
+    max_queued: usize,
+    queued: AtomicUsize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorMessage(pub String);
+
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+// Impls
+impl MutationLimit {
+    pub fn new(max_in_flight: usize, max_queued: usize) -> Self {
+        assert!(max_in_flight > 0, "at least one mutation must be allowed");
+
+        Self {
+            permits: Semaphore::new(max_in_flight),
+            max_in_flight,
+            max_queued,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.max_in_flight - self.permits.available_permits()
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for MutationLimit {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MAX_IN_FLIGHT_MUTATIONS,
+            DEFAULT_MAX_QUEUED_MUTATIONS,
+        )
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Functions
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let header_value = HeaderValue::from_str(&request_id).expect("request id is visible ASCII");
+    request.extensions_mut().insert(RequestId(request_id));
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value);
+
+    response
+}
+
+pub async fn limit_mutations(
+    State(mutation_limit): State<Arc<MutationLimit>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+
+    let _permit = match mutation_limit.permits.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            if mutation_limit.queued.fetch_add(1, Ordering::SeqCst) >= mutation_limit.max_queued {
+                mutation_limit.queued.fetch_sub(1, Ordering::SeqCst);
+                return AppError::TooManyMutations.into_response();
+            }
+
+            let _queued = QueuedGuard(&mutation_limit.queued);
+            mutation_limit
+                .permits
+                .acquire()
+                .await
+                .expect("mutation semaphore is never closed")
+        }
+    };
+
+    next.run(request).await
+}
+
+pub async fn negotiate_errors(request: Request, next: Next) -> Response {
+    let wants_text = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html") && !accept.contains("application/json"));
+
+    let mut response = next.run(request).await;
+    let Some(ErrorMessage(message)) = response.extensions_mut().remove::<ErrorMessage>() else {
+        return response;
+    };
+
+    if !wants_text {
+        return response;
+    }
+
+    (response.status(), message).into_response()
+}
+
+pub async fn format_json(
+    State(pretty_by_default): State<bool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let pretty = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| match pair {
+            "pretty=true" => Some(true),
+            "pretty=false" => Some(false),
+            _ => None,
+        })
+        .unwrap_or(pretty_by_default);
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+    if !pretty || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response").into_response();
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(value) => serde_json::to_vec_pretty(&value).expect("json values always serialize"),
+        Err(_) => bytes.to_vec(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+pub async fn reject_until_ready(
+    State(shared_state): State<SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) && !shared_state.read().await.is_ready
+    {
+        return AppError::NotReady.into_response();
+    }
+
+    next.run(request).await
+}
+
+pub async fn persist_mutations(
+    State(shared_state): State<SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+
+    let response = next.run(request).await;
+    if response.status().is_success() {
+        let mut state = shared_state.write().await;
+        state.persist();
+        state.publish_snapshot();
+    }
+
+    response
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 use uuid::Uuid;
 
 // Enums
@@ -8,6 +11,11 @@ pub enum TodoListFilter {
     Completed,
     Active,
     All,
+    Drafts,
+    Overdue,
+    Dismissed,
+    Manual,
+    Archived,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -16,23 +24,132 @@ pub enum TodoToggleAction {
     Check,
 }
 
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum TodoEvent {
+    Created(Uuid),
+    Updated(Uuid),
+    Deleted(Uuid),
+    Reset,
+}
+
 // Structs
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Todo {
     pub is_completed: bool,
+    #[serde(with = "rfc3339")]
     pub created_at: SystemTime,
+    #[serde(with = "rfc3339", default = "unix_epoch")]
+    pub updated_at: SystemTime,
     pub text: String,
     pub id: Uuid,
+    pub metadata: HashMap<String, String>,
+    #[serde(with = "rfc3339::option", default)]
+    pub expires_at: Option<SystemTime>,
+    pub is_draft: bool,
+    pub ref_num: u32,
+    #[serde(with = "rfc3339::option", default)]
+    pub completed_at: Option<SystemTime>,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub progress: u8,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(with = "rfc3339::option", default)]
+    pub due_at: Option<SystemTime>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(with = "rfc3339::option", default)]
+    pub dismissed_at: Option<SystemTime>,
+    #[serde(default)]
+    pub order: u32,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub recurrence: Option<Duration>,
+    #[serde(default)]
+    pub version: u64,
 }
 
 // Impls
 impl Todo {
     pub fn new(text: impl Into<String>) -> Self {
+        let now = SystemTime::now();
+
         Self {
             is_completed: false,
             text: text.into(),
-            created_at: SystemTime::now(),
+            created_at: now,
+            updated_at: now,
             id: Uuid::new_v4(),
+            metadata: HashMap::new(),
+            expires_at: None,
+            is_draft: false,
+            ref_num: 0,
+            completed_at: None,
+            title: None,
+            progress: 0,
+            priority: Priority::default(),
+            due_at: None,
+            tags: Vec::new(),
+            dismissed_at: None,
+            order: 0,
+            archived: false,
+            recurrence: None,
+            version: 0,
+        }
+    }
+
+    pub fn set_completed(&mut self, is_completed: bool, now: SystemTime) {
+        if self.is_completed != is_completed {
+            self.is_completed = is_completed;
+            self.completed_at = is_completed.then_some(now);
+            if is_completed {
+                self.progress = 100;
+            } else if self.progress == 100 {
+                self.progress = 0;
+            }
+        }
+    }
+
+    pub fn display_text(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.text)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.is_draft || self.dismissed_at.is_some() || self.archived
+    }
+
+    pub fn is_overdue(&self, now: SystemTime) -> bool {
+        !self.is_completed && self.due_at.is_some_and(|due_at| due_at < now)
+    }
+}
+
+impl TodoListFilter {
+    pub fn matches(&self, todo: &Todo) -> bool {
+        match self {
+            Self::Drafts => todo.is_draft,
+            Self::Dismissed => !todo.is_draft && !todo.archived && todo.dismissed_at.is_some(),
+            Self::Archived => !todo.is_draft && todo.archived,
+            _ if todo.is_hidden() => false,
+            Self::All | Self::Manual => true,
+            Self::Completed => todo.is_completed,
+            Self::Active => !todo.is_completed,
+            Self::Overdue => todo.is_overdue(SystemTime::now()),
         }
     }
 }
@@ -43,6 +160,11 @@ impl std::fmt::Display for TodoListFilter {
             Self::Completed => write!(f, "Completed"),
             Self::Active => write!(f, "Active"),
             Self::All => write!(f, "All"),
+            Self::Overdue => write!(f, "Overdue"),
+            Self::Drafts => write!(f, "Drafts"),
+            Self::Dismissed => write!(f, "Dismissed"),
+            Self::Manual => write!(f, "Manual"),
+            Self::Archived => write!(f, "Archived"),
         }
     }
 }
@@ -55,3 +177,102 @@ impl std::fmt::Display for TodoToggleAction {
         }
     }
 }
+
+// Functions
+pub fn normalize_tags<I, S>(tags: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.as_ref().trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    normalized
+}
+
+pub fn truncate_to_millis(time: SystemTime) -> SystemTime {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let millis = Duration::from_millis(since_epoch.as_millis() as u64);
+
+    SystemTime::UNIX_EPOCH + millis
+}
+
+pub fn derive_title(text: &str, max_len: usize) -> String {
+    let text = text.trim();
+    let Some((cut, _)) = text.char_indices().nth(max_len) else {
+        return text.to_string();
+    };
+
+    let head = &text[..cut];
+    let head = match head.rfind(char::is_whitespace) {
+        Some(boundary) if !text[cut..].starts_with(char::is_whitespace) => &head[..boundary],
+        _ => head,
+    };
+
+    format!("{}…", head.trim_end())
+}
+
+fn unix_epoch() -> SystemTime {
+    SystemTime::UNIX_EPOCH
+}
+
+mod rfc3339 {
+    use std::time::SystemTime;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Rfc3339(String),
+        Legacy(SystemTime),
+    }
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(&humantime::format_rfc3339_nanos(*time))
+        } else {
+            time.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        if !deserializer.is_human_readable() {
+            return SystemTime::deserialize(deserializer);
+        }
+
+        match Timestamp::deserialize(deserializer)? {
+            Timestamp::Rfc3339(time) => humantime::parse_rfc3339(&time).map_err(D::Error::custom),
+            Timestamp::Legacy(time) => Ok(time),
+        }
+    }
+
+    pub mod option {
+        use std::time::SystemTime;
+
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        #[derive(Serialize, Deserialize)]
+        struct Timestamp(#[serde(with = "super")] SystemTime);
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<SystemTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            time.map(Timestamp).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<SystemTime>, D::Error> {
+            Ok(Option::<Timestamp>::deserialize(deserializer)?.map(|Timestamp(time)| time))
+        }
+    }
+}
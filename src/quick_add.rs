@@ -0,0 +1,137 @@
+use std::time::{Duration, SystemTime};
+
+use crate::models::Priority;
+
+// Consts
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+// Structs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickAdd {
+    pub text: String,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    pub due_at: Option<SystemTime>,
+}
+
+// Functions
+pub fn parse_quick_add(input: &str, now: SystemTime) -> QuickAdd {
+    let mut quick_add = QuickAdd {
+        text: String::new(),
+        tags: Vec::new(),
+        priority: None,
+        due_at: None,
+    };
+    let mut words = Vec::new();
+
+    for word in input.split_whitespace() {
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            quick_add.tags.push(tag.to_lowercase());
+        } else if let Some(priority) = word.strip_prefix('!').and_then(parse_priority) {
+            quick_add.priority = Some(priority);
+        } else if let Some(due_at) = parse_due(word, now) {
+            quick_add.due_at = Some(due_at);
+        } else {
+            words.push(word);
+        }
+    }
+
+    quick_add.text = words.join(" ");
+    quick_add
+}
+
+fn parse_priority(word: &str) -> Option<Priority> {
+    match word.to_lowercase().as_str() {
+        "low" => Some(Priority::Low),
+        "medium" => Some(Priority::Medium),
+        "high" => Some(Priority::High),
+        _ => None,
+    }
+}
+
+fn parse_due(word: &str, now: SystemTime) -> Option<SystemTime> {
+    let today = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / DAY_SECS;
+
+    let day = match word.to_lowercase().as_str() {
+        "today" => today,
+        "tomorrow" => today + 1,
+        date => {
+            let midnight = humantime::parse_rfc3339(&format!("{date}T00:00:00Z")).ok()?;
+            midnight
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()?
+                .as_secs()
+                / DAY_SECS
+        }
+    };
+
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs((day + 1) * DAY_SECS - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_quick_add() {
+        let now = humantime::parse_rfc3339("2024-03-10T15:30:00Z").unwrap();
+
+        let parsed = parse_quick_add("Buy milk #shopping !high tomorrow", now);
+
+        assert_eq!(
+            QuickAdd {
+                text: "Buy milk".into(),
+                tags: vec!["shopping".into()],
+                priority: Some(Priority::High),
+                due_at: humantime::parse_rfc3339("2024-03-11T23:59:59Z").ok(),
+            },
+            parsed
+        );
+    }
+
+    #[test]
+    fn test_parse_strips_tags_from_text() {
+        let now = SystemTime::now();
+
+        let parsed = parse_quick_add("Call #Home the plumber #urgent", now);
+
+        assert_eq!("Call the plumber", parsed.text);
+        assert_eq!(vec!["home", "urgent"], parsed.tags);
+    }
+
+    #[test]
+    fn test_parse_bare_text() {
+        let now = SystemTime::now();
+
+        let parsed = parse_quick_add("  Call   the plumber ", now);
+
+        assert_eq!(
+            QuickAdd {
+                text: "Call the plumber".into(),
+                tags: vec![],
+                priority: None,
+                due_at: None,
+            },
+            parsed
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_unknown_tokens() {
+        let now = humantime::parse_rfc3339("2024-03-10T15:30:00Z").unwrap();
+
+        let parsed = parse_quick_add("Ship it !urgent # 2024-03-15", now);
+
+        assert_eq!("Ship it !urgent #", parsed.text);
+        assert!(parsed.tags.is_empty());
+        assert_eq!(None, parsed.priority);
+        assert_eq!(
+            humantime::parse_rfc3339("2024-03-15T23:59:59Z").ok(),
+            parsed.due_at
+        );
+    }
+}
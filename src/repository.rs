@@ -1,289 +1,3961 @@
-use crate::models::{Todo, TodoListFilter, TodoToggleAction};
-use std::collections::HashMap;
+use crate::{
+    csv::{parse_records, write_record, ImportError},
+    models::{
+        derive_title, normalize_tags, truncate_to_millis, Priority, Todo, TodoListFilter,
+        TodoToggleAction,
+    },
+};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Write},
+    mem::size_of,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
 use uuid::Uuid;
 
+// Consts
+pub const MAX_PATTERN_LEN: usize = 256;
+pub const MAX_METADATA_KEYS: usize = 32;
+pub const MAX_METADATA_KEY_LEN: usize = 64;
+pub const MAX_METADATA_VALUE_LEN: usize = 1024;
+pub const SNAPSHOT_VERSION: u8 = 12;
+pub const MAX_PROGRESS: u8 = 100;
+pub const RESTORE_TTL: Duration = Duration::from_secs(5 * 60);
+pub const DEFAULT_MAX_TEXT_LEN: usize = 500;
+const PATTERN_SIZE_LIMIT: usize = 1 << 16;
+const MIN_JOURNAL_COMPACT_BYTES: u64 = 64 * 1024;
+const DAY_SECS: i64 = 24 * 60 * 60;
+const DAY: Duration = Duration::from_secs(DAY_SECS as u64);
+const AGE_BUCKETS: [(&str, Duration); 3] = [
+    ("<1d", DAY),
+    ("1-7d", Duration::from_secs(7 * 24 * 60 * 60)),
+    ("7-30d", Duration::from_secs(30 * 24 * 60 * 60)),
+];
+const OLDEST_AGE_BUCKET: &str = "30d+";
+const FORECAST_WINDOW_DAYS: i64 = 14;
+const LINT_MAX_TEXT_LEN: usize = 1000;
+const CSV_HEADER: [&str; 4] = ["id", "text", "is_completed", "created_at"];
+const LINT_MAX_OVERDUE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
 // Enums
 #[derive(Debug, PartialEq, Eq)]
 pub enum TodoRepoError {
     NotFound,
+    InvalidPattern,
+    Ambiguous,
+    MetadataKeyNotFound,
+    MetadataTooLarge,
+    DuplicateText,
+    DuplicateIds(Vec<Uuid>),
+    InvalidProgress,
+    InvalidInput,
+    TextTooLong { max: usize },
+    TagNotAllowed(Vec<String>),
+    InvalidCursor,
+    InvalidTimestamp,
+    VersionConflict { current: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "todo", rename_all = "snake_case")]
+pub enum TodoChange {
+    Saved(Box<Todo>),
+    Deleted(Uuid),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LintWarning {
+    BlankText,
+    TextTooLong,
+    LongOverdue,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    UnsupportedVersion(u8),
+    Malformed,
+}
+
+// Structs
+#[derive(Debug, Default)]
+pub struct TodoRepo {
+    pub num_completed_items: u32,
+    pub num_active_items: u32,
+    pub num_all_items: u32,
+    pub last_ref_num: u32,
+    pub truncate_timestamps: bool,
+    pub auto_title_len: Option<usize>,
+    pub max_completed_retained: Option<usize>,
+    /// Off by default: clearing completed todos archives them instead.
+    pub hard_delete_completed: bool,
+    pub items: HashMap<Uuid, Todo>,
+    pub dirty: HashSet<Uuid>,
+    pub recently_deleted: Vec<(Instant, Todo)>,
+    /// Generation of the snapshot on disk; journal entries carry it so a
+    /// replay can skip entries already folded into a newer snapshot.
+    pub snapshot_generation: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RepoDiff {
+    pub added: Vec<Uuid>,
+    pub removed: Vec<Uuid>,
+    pub changed: Vec<(Uuid, Vec<&'static str>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TodoStats {
+    pub num_all: u32,
+    pub num_active: u32,
+    pub num_completed: u32,
+    pub completion_percentage: f64,
+    pub created_last_24h: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TodoRow {
+    pub id: Uuid,
+    pub ref_num: u32,
+    pub text: String,
+    pub is_completed: bool,
+    pub priority: Priority,
+    pub progress: u8,
+    pub tags: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub completed_at: Option<String>,
+    pub due_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub created_at: SystemTime,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoQuery {
+    pub filter: TodoListFilter,
+    pub text: Option<String>,
+    pub pattern: Option<String>,
+    pub priority: Option<Priority>,
+    pub tag: Option<String>,
+    pub due_after: Option<SystemTime>,
+    pub due_before: Option<SystemTime>,
+    pub created_after: Option<SystemTime>,
+    pub created_before: Option<SystemTime>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TodoPatch {
+    pub text: Option<String>,
+    pub is_completed: Option<bool>,
+    pub progress: Option<u8>,
+    pub priority: Option<Priority>,
+    pub due_at: Option<SystemTime>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    last_ref_num: u32,
+    #[serde(default)]
+    num_completed_items: u32,
+    #[serde(default)]
+    num_active_items: u32,
+    #[serde(default)]
+    num_all_items: u32,
+    items: Vec<Todo>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SnapshotFile {
+    #[serde(default)]
+    generation: u64,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    generation: u64,
+    #[serde(flatten)]
+    change: TodoChange,
 }
 
-// Structs
-#[derive(Debug, Default)]
-pub struct TodoRepo {
-    pub num_completed_items: u32,
-    pub num_active_items: u32,
-    pub num_all_items: u32,
-    pub items: HashMap<Uuid, Todo>,
-}
+// Impls
+impl TodoRepo {
+    pub fn get(&self, id: &Uuid) -> Result<Todo, TodoRepoError> {
+        self.items.get(id).ok_or(TodoRepoError::NotFound).cloned()
+    }
+
+    pub fn get_by_ref(&self, ref_num: u32) -> Result<Todo, TodoRepoError> {
+        self.items
+            .values()
+            .find(|t| t.ref_num == ref_num)
+            .ok_or(TodoRepoError::NotFound)
+            .cloned()
+    }
+
+    pub fn get_by_prefix(&self, prefix: &str) -> Result<Todo, TodoRepoError> {
+        let prefix = prefix.replace('-', "").to_lowercase();
+        if prefix.is_empty() {
+            return Err(TodoRepoError::NotFound);
+        }
+
+        let mut matches = self
+            .items
+            .values()
+            .filter(|t| t.id.simple().to_string().starts_with(&prefix));
+
+        match (matches.next(), matches.next()) {
+            (Some(todo), None) => Ok(todo.clone()),
+            (Some(_), Some(_)) => Err(TodoRepoError::Ambiguous),
+            (None, _) => Err(TodoRepoError::NotFound),
+        }
+    }
+
+    pub fn list(&self, filter: &TodoListFilter) -> Vec<Todo> {
+        self.select(filter, |_| true)
+    }
+
+    pub fn query(&self, query: &TodoQuery) -> Result<Vec<Todo>, TodoRepoError> {
+        let regex = query.pattern.as_deref().map(compile_pattern).transpose()?;
+        let text = query
+            .text
+            .as_deref()
+            .map(|text| text.trim().to_lowercase())
+            .filter(|text| !text.is_empty());
+        let tag = query.tag.as_deref().map(|tag| tag.trim().to_lowercase());
+
+        Ok(self.select(&query.filter, |t| {
+            query.priority.is_none_or(|priority| t.priority == priority)
+                && tag.as_ref().is_none_or(|tag| t.tags.contains(tag))
+                && text
+                    .as_ref()
+                    .is_none_or(|text| t.text.to_lowercase().contains(text))
+                && regex.as_ref().is_none_or(|regex| regex.is_match(&t.text))
+                && query
+                    .due_after
+                    .is_none_or(|after| t.due_at.is_some_and(|due| due >= after))
+                && query
+                    .due_before
+                    .is_none_or(|before| t.due_at.is_some_and(|due| due <= before))
+                && query
+                    .created_after
+                    .is_none_or(|after| t.created_at >= after)
+                && query
+                    .created_before
+                    .is_none_or(|before| t.created_at <= before)
+        }))
+    }
+
+    pub fn list_in_range(
+        &self,
+        filter: &TodoListFilter,
+        after: Option<SystemTime>,
+        before: Option<SystemTime>,
+    ) -> Vec<Todo> {
+        self.select(filter, |t| {
+            after.is_none_or(|after| t.created_at >= after)
+                && before.is_none_or(|before| t.created_at <= before)
+        })
+    }
+
+    fn select<F: Fn(&Todo) -> bool>(&self, filter: &TodoListFilter, pred: F) -> Vec<Todo> {
+        let now = SystemTime::now();
+        let mut todos: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| !t.is_expired(now) && filter.matches(t) && pred(t))
+            .cloned()
+            .collect();
+
+        if *filter == TodoListFilter::Manual {
+            todos.sort_by_key(|t| (t.order, t.ref_num));
+        } else {
+            todos.sort_by_key(list_order);
+        }
+
+        todos
+    }
+
+    pub fn reorder(&mut self, id: &Uuid, new_position: usize) -> Result<(), TodoRepoError> {
+        let todos = self.list(&TodoListFilter::Manual);
+        let index = todos
+            .iter()
+            .position(|t| t.id == *id)
+            .ok_or(TodoRepoError::NotFound)?;
+
+        let orders: Vec<_> = todos.iter().map(|t| t.order).collect();
+        let mut ids: Vec<_> = todos.iter().map(|t| t.id).collect();
+        let moved = ids.remove(index);
+        ids.insert(new_position.min(ids.len()), moved);
+
+        for (id, order) in ids.into_iter().zip(orders) {
+            if self.items[&id].order != order {
+                self.mutate(&id, |todo| todo.order = order)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn list_paged(
+        &self,
+        filter: &TodoListFilter,
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<Todo>, usize) {
+        paginate(self.list(filter), offset, limit)
+    }
+
+    pub fn list_recently_updated(&self, limit: usize) -> Vec<Todo> {
+        let mut todos = self.list(&TodoListFilter::All);
+        todos.sort_by_key(|t| Reverse(t.updated_at));
+        todos.truncate(limit);
+
+        todos
+    }
+
+    pub fn list_after(
+        &self,
+        filter: &TodoListFilter,
+        after: Option<Cursor>,
+        limit: usize,
+    ) -> (Vec<Todo>, Option<Cursor>) {
+        let now = SystemTime::now();
+        let mut todos: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| !t.is_expired(now) && filter.matches(t))
+            .filter(|t| after.is_none_or(|after| Cursor::of(t) < after))
+            .cloned()
+            .collect();
+        todos.sort_by_key(|t| Reverse(Cursor::of(t)));
+
+        let has_more = todos.len() > limit;
+        todos.truncate(limit);
+        let next_cursor = todos.last().filter(|_| has_more).map(Cursor::of);
+
+        (todos, next_cursor)
+    }
+
+    pub fn focus(&self, min_priority: Priority) -> Vec<Todo> {
+        let mut todos = self.list(&TodoListFilter::Active);
+        todos.retain(|t| t.priority >= min_priority);
+        todos.sort_by_key(|t| (Reverse(t.priority), t.due_at.is_none(), t.due_at));
+
+        todos
+    }
+
+    pub fn list_by_tag(&self, tag: &str) -> Vec<Todo> {
+        let tag = tag.trim().to_lowercase();
+        self.select(&TodoListFilter::All, |t| t.tags.contains(&tag))
+    }
+
+    pub fn search(&self, query: &str, filter: &TodoListFilter) -> Vec<Todo> {
+        let query = query.trim().to_lowercase();
+        self.select(filter, |t| t.text.to_lowercase().contains(&query))
+    }
+
+    pub fn list_matching(
+        &self,
+        filter: &TodoListFilter,
+        pattern: &str,
+    ) -> Result<Vec<Todo>, TodoRepoError> {
+        let regex = compile_pattern(pattern)?;
+        Ok(self.select(filter, |t| regex.is_match(&t.text)))
+    }
+
+    pub fn partition<F: Fn(&Todo) -> bool>(&self, pred: F) -> (Vec<Todo>, Vec<Todo>) {
+        let now = SystemTime::now();
+        let (mut matching, mut rest) = (Vec::new(), Vec::new());
+
+        for todo in self
+            .items
+            .values()
+            .filter(|t| !t.is_expired(now) && TodoListFilter::All.matches(t))
+        {
+            if pred(todo) {
+                matching.push(todo.clone());
+            } else {
+                rest.push(todo.clone());
+            }
+        }
+
+        matching.sort_by_key(list_order);
+        rest.sort_by_key(list_order);
+
+        (matching, rest)
+    }
+
+    pub fn age_distribution(&self, now: SystemTime) -> Vec<(&'static str, u32)> {
+        let mut buckets: Vec<_> = AGE_BUCKETS
+            .iter()
+            .map(|(name, _)| (*name, 0))
+            .chain([(OLDEST_AGE_BUCKET, 0)])
+            .collect();
+
+        for todo in self
+            .items
+            .values()
+            .filter(|t| !t.is_expired(now) && TodoListFilter::Active.matches(t))
+        {
+            let age = now.duration_since(todo.created_at).unwrap_or_default();
+            let bucket = AGE_BUCKETS
+                .iter()
+                .position(|(_, limit)| age < *limit)
+                .unwrap_or(AGE_BUCKETS.len());
+
+            buckets[bucket].1 += 1;
+        }
+
+        buckets
+    }
+
+    pub fn stats(&self, now: SystemTime) -> TodoStats {
+        let completion_percentage = if self.num_all_items == 0 {
+            0.0
+        } else {
+            f64::from(self.num_completed_items) * 100.0 / f64::from(self.num_all_items)
+        };
+        let created_last_24h = self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden())
+            .filter(|t| now.duration_since(t.created_at).is_ok_and(|age| age < DAY))
+            .count() as u32;
+
+        TodoStats {
+            num_all: self.num_all_items,
+            num_active: self.num_active_items,
+            num_completed: self.num_completed_items,
+            completion_percentage,
+            created_last_24h,
+        }
+    }
+
+    pub fn completions_by_hour(&self, tz_offset_minutes: i32) -> [u32; 24] {
+        let offset = i64::from(tz_offset_minutes) * 60;
+        let mut hours = [0; 24];
+
+        for completed_at in self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden())
+            .filter_map(|t| t.completed_at)
+        {
+            let hour = (unix_secs(completed_at) + offset).rem_euclid(DAY_SECS) / (60 * 60);
+            hours[hour as usize] += 1;
+        }
+
+        hours
+    }
+
+    pub fn burndown(
+        &self,
+        now: SystemTime,
+        days: u32,
+        tz_offset_minutes: i32,
+    ) -> Vec<(String, u32)> {
+        let offset = i64::from(tz_offset_minutes) * 60;
+        let today = (unix_secs(now) + offset).div_euclid(DAY_SECS);
+
+        (0..i64::from(days))
+            .rev()
+            .map(|days_ago| {
+                let day = today - days_ago;
+                let end_of_day = (day + 1) * DAY_SECS - offset;
+
+                let remaining = self
+                    .items
+                    .values()
+                    .filter(|t| !t.is_hidden() && unix_secs(t.created_at) < end_of_day)
+                    .filter(|t| {
+                        t.completed_at
+                            .is_none_or(|completed_at| unix_secs(completed_at) >= end_of_day)
+                    })
+                    .count();
+
+                (format_day(day), remaining as u32)
+            })
+            .collect()
+    }
+
+    pub fn forecast_completion(
+        &self,
+        now: SystemTime,
+        tz_offset_minutes: i32,
+    ) -> Option<SystemTime> {
+        let offset = i64::from(tz_offset_minutes) * 60;
+        let start_of_today = (unix_secs(now) + offset).div_euclid(DAY_SECS) * DAY_SECS - offset;
+        let window = (start_of_today - FORECAST_WINDOW_DAYS * DAY_SECS)..start_of_today;
+
+        let completed = self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden())
+            .filter_map(|t| t.completed_at)
+            .filter(|completed_at| window.contains(&unix_secs(*completed_at)))
+            .count() as u64;
+        if completed == 0 {
+            return None;
+        }
+
+        let remaining_secs =
+            u64::from(self.num_active_items) * FORECAST_WINDOW_DAYS as u64 * DAY_SECS as u64
+                / completed;
+
+        Some(now + Duration::from_secs(remaining_secs))
+    }
+
+    pub fn validate_text(&self, text: &str, max_len: usize) -> Result<(), TodoRepoError> {
+        if text.trim().is_empty() {
+            return Err(TodoRepoError::InvalidInput);
+        }
+
+        if text.chars().count() > max_len {
+            return Err(TodoRepoError::TextTooLong { max: max_len });
+        }
+
+        Ok(())
+    }
+
+    pub fn ensure_unique_text(
+        &self,
+        text: &str,
+        except: Option<&Uuid>,
+    ) -> Result<(), TodoRepoError> {
+        let text = normalize_text(text);
+        let now = SystemTime::now();
+        let is_duplicate = self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden() && !t.is_expired(now) && Some(&t.id) != except)
+            .any(|t| normalize_text(&t.text) == text);
+
+        if is_duplicate {
+            return Err(TodoRepoError::DuplicateText);
+        }
+
+        Ok(())
+    }
+
+    pub fn ensure_unique_texts<S: AsRef<str>>(&self, texts: &[S]) -> Result<(), TodoRepoError> {
+        let mut seen = HashSet::new();
+        for text in texts {
+            self.ensure_unique_text(text.as_ref(), None)?;
+            if !seen.insert(normalize_text(text.as_ref())) {
+                return Err(TodoRepoError::DuplicateText);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn create(&mut self, text: impl Into<String>) -> Todo {
+        self.create_with_priority(text, None)
+    }
+
+    pub fn create_with_priority(
+        &mut self,
+        text: impl Into<String>,
+        priority: Option<Priority>,
+    ) -> Todo {
+        let mut todo = Todo::new(text);
+        todo.priority = priority.unwrap_or_default();
+        let todo = self.insert_new(todo);
+        self.recount();
+
+        todo
+    }
+
+    pub fn find_active_by_text(&self, text: &str) -> Option<Todo> {
+        let text = normalize_text(text);
+        let now = SystemTime::now();
+
+        self.items
+            .values()
+            .filter(|t| !t.is_hidden() && !t.is_expired(now) && !t.is_completed)
+            .find(|t| normalize_text(&t.text) == text)
+            .cloned()
+    }
+
+    pub fn create_unique(&mut self, text: impl Into<String>) -> (Todo, bool) {
+        let text = text.into();
+        match self.find_active_by_text(&text) {
+            Some(existing) => (existing, false),
+            None => (self.create(text), true),
+        }
+    }
+
+    pub fn create_many(&mut self, texts: Vec<String>) -> Vec<Todo> {
+        let todos = texts
+            .into_iter()
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| self.insert_new(Todo::new(text)))
+            .collect();
+        self.recount();
+
+        todos
+    }
+
+    pub fn create_with_tags(&mut self, text: impl Into<String>, tags: Vec<String>) -> Todo {
+        let mut todo = Todo::new(text);
+        todo.tags = tags;
+        let todo = self.insert_new(todo);
+        self.recount();
+
+        todo
+    }
+
+    pub fn create_draft(&mut self, text: impl Into<String>) -> Todo {
+        let mut todo = Todo::new(text);
+        todo.is_draft = true;
+
+        self.insert_new(todo)
+    }
+
+    fn now(&self) -> SystemTime {
+        if self.truncate_timestamps {
+            truncate_to_millis(SystemTime::now())
+        } else {
+            SystemTime::now()
+        }
+    }
+
+    fn insert_new(&mut self, mut todo: Todo) -> Todo {
+        if self.truncate_timestamps {
+            todo.created_at = truncate_to_millis(todo.created_at);
+            todo.updated_at = truncate_to_millis(todo.updated_at);
+            todo.expires_at = todo.expires_at.map(truncate_to_millis);
+            todo.dismissed_at = todo.dismissed_at.map(truncate_to_millis);
+            todo.completed_at = todo.completed_at.map(truncate_to_millis);
+            todo.due_at = todo.due_at.map(truncate_to_millis);
+        }
+
+        todo.title = self.auto_title_len.map(|len| derive_title(&todo.text, len));
+        todo.tags = normalize_tags(&todo.tags);
+        todo.updated_at = todo.updated_at.max(todo.created_at);
+
+        self.last_ref_num += 1;
+        todo.ref_num = self.last_ref_num;
+        todo.order = self.last_ref_num;
+        self.items.insert(todo.id, todo.clone());
+        self.dirty.insert(todo.id);
+
+        todo
+    }
+
+    pub fn publish(&mut self, id: &Uuid) -> Result<Todo, TodoRepoError> {
+        let todo = self.get(id)?;
+        if !todo.is_draft {
+            return Ok(todo);
+        }
+
+        let todo = self.mutate(id, |todo| todo.is_draft = false)?;
+        self.recount();
+
+        Ok(todo)
+    }
+
+    pub fn dismiss(&mut self, id: &Uuid) -> Result<Todo, TodoRepoError> {
+        let now = self.now();
+        let todo = self.mutate(id, |todo| {
+            todo.dismissed_at.get_or_insert(now);
+        })?;
+        self.recount();
+
+        Ok(todo)
+    }
+
+    pub fn restore(&mut self, id: &Uuid) -> Result<Todo, TodoRepoError> {
+        self.purge_deleted(Instant::now());
+
+        if !self.items.contains_key(id) {
+            let index = self
+                .recently_deleted
+                .iter()
+                .position(|(_, t)| t.id == *id)
+                .ok_or(TodoRepoError::NotFound)?;
+            let (_, todo) = self.recently_deleted.remove(index);
+            self.items.insert(todo.id, todo);
+        }
+        let todo = self.mutate(id, |todo| todo.dismissed_at = None)?;
+        self.recount();
+
+        Ok(todo)
+    }
+
+    pub fn delete(&mut self, id: &Uuid) -> Result<(), TodoRepoError> {
+        let todo = self.items.remove(id).ok_or(TodoRepoError::NotFound)?;
+        self.dirty.insert(*id);
+        self.recount();
+
+        let now = Instant::now();
+        self.purge_deleted(now);
+        self.recently_deleted.push((now, todo));
+
+        Ok(())
+    }
+
+    fn purge_deleted(&mut self, now: Instant) {
+        self.recently_deleted
+            .retain(|(deleted_at, _)| now.duration_since(*deleted_at) < RESTORE_TTL);
+    }
+
+    pub fn update(
+        &mut self,
+        id: &Uuid,
+        text: Option<String>,
+        is_completed: Option<bool>,
+        expected_version: Option<u64>,
+    ) -> Result<Todo, TodoRepoError> {
+        let patch = TodoPatch {
+            text,
+            is_completed,
+            ..TodoPatch::default()
+        };
+
+        self.patch(id, patch, expected_version)
+            .map(|(todo, _)| todo)
+    }
+
+    pub fn complete_and_recur(&mut self, id: &Uuid) -> Result<(Todo, Option<Todo>), TodoRepoError> {
+        let patch = TodoPatch {
+            is_completed: Some(true),
+            ..TodoPatch::default()
+        };
+
+        self.patch(id, patch, None)
+    }
+
+    pub fn ensure_version(
+        &self,
+        id: &Uuid,
+        expected_version: Option<u64>,
+    ) -> Result<(), TodoRepoError> {
+        let current = self.items.get(id).ok_or(TodoRepoError::NotFound)?.version;
+        match expected_version {
+            Some(expected) if expected != current => {
+                Err(TodoRepoError::VersionConflict { current })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn patch(
+        &mut self,
+        id: &Uuid,
+        patch: TodoPatch,
+        expected_version: Option<u64>,
+    ) -> Result<(Todo, Option<Todo>), TodoRepoError> {
+        let TodoPatch {
+            text,
+            is_completed,
+            progress,
+            priority,
+            due_at,
+            tags,
+        } = patch;
+        if progress.is_some_and(|progress| progress > MAX_PROGRESS) {
+            return Err(TodoRepoError::InvalidProgress);
+        }
+        self.ensure_version(id, expected_version)?;
+
+        let now = self.now();
+        let auto_title_len = self.auto_title_len;
+        let due_at = if self.truncate_timestamps {
+            due_at.map(truncate_to_millis)
+        } else {
+            due_at
+        };
+        let is_completed = is_completed.or(progress.map(|progress| progress == MAX_PROGRESS));
+        let mut recurrence = None;
+
+        let todo = self.mutate(id, |todo| {
+            if let Some(progress) = progress {
+                todo.progress = progress;
+            }
+
+            if let Some(completed) = is_completed
+                && todo.is_completed != completed
+            {
+                todo.set_completed(completed, now);
+                todo.updated_at = now;
+                recurrence = todo.recurrence.filter(|_| completed);
+            }
+
+            if let Some(text) = text
+                && todo.text != text
+            {
+                todo.title = auto_title_len.map(|len| derive_title(&text, len));
+                todo.text = text;
+                todo.updated_at = now;
+            }
+
+            if let Some(priority) = priority {
+                todo.priority = priority;
+            }
+            if let Some(due_at) = due_at {
+                todo.due_at = Some(due_at);
+            }
+            if let Some(tags) = tags {
+                todo.tags = normalize_tags(tags);
+            }
+        })?;
+        let next = recurrence.map(|recurrence| {
+            let mut next = Todo::new(todo.text.clone());
+            next.tags = todo.tags.clone();
+            next.priority = todo.priority;
+            next.recurrence = Some(recurrence);
+            next.due_at = Some(now + recurrence);
+            self.insert_new(next)
+        });
+        if is_completed == Some(true) {
+            self.trim_completed();
+        }
+        self.recount();
+
+        Ok((todo, next))
+    }
+
+    fn mutate(&mut self, id: &Uuid, edit: impl FnOnce(&mut Todo)) -> Result<Todo, TodoRepoError> {
+        let todo = self.items.get_mut(id).ok_or(TodoRepoError::NotFound)?;
+        edit(todo);
+        todo.version += 1;
+        self.dirty.insert(*id);
+
+        Ok(todo.clone())
+    }
+
+    pub fn set_recurrence(
+        &mut self,
+        id: &Uuid,
+        recurrence: Option<Duration>,
+    ) -> Result<Todo, TodoRepoError> {
+        self.mutate(id, |todo| todo.recurrence = recurrence)
+    }
+
+    pub fn set_progress(&mut self, id: &Uuid, progress: u8) -> Result<Todo, TodoRepoError> {
+        let patch = TodoPatch {
+            progress: Some(progress),
+            ..TodoPatch::default()
+        };
+
+        self.patch(id, patch, None).map(|(todo, _)| todo)
+    }
+
+    pub fn set_priority(&mut self, id: &Uuid, priority: Priority) -> Result<Todo, TodoRepoError> {
+        self.mutate(id, |todo| todo.priority = priority)
+    }
+
+    pub fn set_tags(&mut self, id: &Uuid, tags: Vec<String>) -> Result<Todo, TodoRepoError> {
+        self.mutate(id, |todo| todo.tags = normalize_tags(tags))
+    }
+
+    pub fn clear_tags(&mut self, ids: &[Uuid]) -> Vec<Uuid> {
+        let mut missing = Vec::new();
+        for id in ids {
+            match self.items.get(id) {
+                Some(todo) if !todo.tags.is_empty() => {
+                    self.mutate(id, |todo| todo.tags.clear())
+                        .expect("todo exists");
+                }
+                Some(_) => {}
+                None => missing.push(*id),
+            }
+        }
+
+        missing
+    }
+
+    pub fn get_metadata(&self, id: &Uuid, key: &str) -> Result<String, TodoRepoError> {
+        let todo = self.items.get(id).ok_or(TodoRepoError::NotFound)?;
+
+        todo.metadata
+            .get(key)
+            .cloned()
+            .ok_or(TodoRepoError::MetadataKeyNotFound)
+    }
+
+    pub fn set_metadata(
+        &mut self,
+        id: &Uuid,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Todo, TodoRepoError> {
+        let todo = self.items.get(id).ok_or(TodoRepoError::NotFound)?;
+        let (key, value) = (key.into(), value.into());
+
+        let is_new_key = !todo.metadata.contains_key(&key);
+        if key.is_empty()
+            || key.chars().count() > MAX_METADATA_KEY_LEN
+            || value.chars().count() > MAX_METADATA_VALUE_LEN
+            || (is_new_key && todo.metadata.len() >= MAX_METADATA_KEYS)
+        {
+            return Err(TodoRepoError::MetadataTooLarge);
+        }
+
+        self.mutate(id, |todo| {
+            todo.metadata.insert(key, value);
+        })
+    }
+
+    pub fn delete_metadata(&mut self, id: &Uuid, key: &str) -> Result<Todo, TodoRepoError> {
+        let todo = self.items.get(id).ok_or(TodoRepoError::NotFound)?;
+        if !todo.metadata.contains_key(key) {
+            return Err(TodoRepoError::MetadataKeyNotFound);
+        }
+
+        self.mutate(id, |todo| {
+            todo.metadata.remove(key);
+        })
+    }
+
+    pub fn set_expiry(
+        &mut self,
+        id: &Uuid,
+        expires_at: Option<SystemTime>,
+    ) -> Result<Todo, TodoRepoError> {
+        let expires_at = if self.truncate_timestamps {
+            expires_at.map(truncate_to_millis)
+        } else {
+            expires_at
+        };
+
+        let todo = self.mutate(id, |todo| todo.expires_at = expires_at)?;
+        self.recount();
+
+        Ok(todo)
+    }
+
+    pub fn set_due(
+        &mut self,
+        id: &Uuid,
+        due_at: Option<SystemTime>,
+    ) -> Result<Todo, TodoRepoError> {
+        let due_at = if self.truncate_timestamps {
+            due_at.map(truncate_to_millis)
+        } else {
+            due_at
+        };
+
+        self.mutate(id, |todo| todo.due_at = due_at)
+    }
+
+    pub fn overdue(&self, now: SystemTime) -> Vec<Todo> {
+        let mut todos: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden() && !t.is_expired(now) && t.is_overdue(now))
+            .cloned()
+            .collect();
+
+        todos.sort_by_key(|t| t.due_at);
+
+        todos
+    }
+
+    pub fn delete_expired(&mut self, now: SystemTime) -> usize {
+        let expired: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| t.is_expired(now))
+            .map(|t| t.id)
+            .collect();
+
+        for id in &expired {
+            let _ = self.delete(id);
+        }
+
+        expired.len()
+    }
+
+    pub fn lint(&self) -> Vec<(Uuid, Vec<LintWarning>)> {
+        let now = SystemTime::now();
+        let mut todos: Vec<_> = self.items.values().collect();
+        todos.sort_by_key(|t| t.ref_num);
+
+        todos
+            .into_iter()
+            .filter_map(|todo| {
+                let mut warnings = Vec::new();
+                if todo.text.trim().is_empty() {
+                    warnings.push(LintWarning::BlankText);
+                }
+                if todo.text.chars().count() > LINT_MAX_TEXT_LEN {
+                    warnings.push(LintWarning::TextTooLong);
+                }
+                if todo.is_overdue(now)
+                    && todo
+                        .due_at
+                        .and_then(|due_at| now.duration_since(due_at).ok())
+                        .is_some_and(|overdue| overdue > LINT_MAX_OVERDUE)
+                {
+                    warnings.push(LintWarning::LongOverdue);
+                }
+
+                (!warnings.is_empty()).then_some((todo.id, warnings))
+            })
+            .collect()
+    }
+
+    /// Estimate only: every owned heap buffer (text, title, tags, metadata)
+    /// plus a fixed per-entry overhead.
+    pub fn approx_size_bytes(&self) -> usize {
+        self.items
+            .values()
+            .map(|t| {
+                let title = t.title.as_ref().map_or(0, String::len);
+                let tags: usize = t
+                    .tags
+                    .iter()
+                    .map(|tag| size_of::<String>() + tag.len())
+                    .sum();
+                let metadata: usize = t
+                    .metadata
+                    .iter()
+                    .map(|(key, value)| 2 * size_of::<String>() + key.len() + value.len())
+                    .sum();
+
+                size_of::<Uuid>() + size_of::<Todo>() + t.text.len() + title + tags + metadata
+            })
+            .sum()
+    }
+
+    pub fn export(&self) -> Vec<Todo> {
+        let mut todos: Vec<_> = self.items.values().cloned().collect();
+        todos.sort_by_key(|t| Reverse(t.created_at));
+
+        todos
+    }
+
+    pub fn import(&mut self, todos: Vec<Todo>) -> Result<usize, TodoRepoError> {
+        let mut seen = HashSet::new();
+        let mut collisions: Vec<_> = todos
+            .iter()
+            .map(|t| t.id)
+            .filter(|id| !seen.insert(*id) || self.items.contains_key(id))
+            .collect();
+        if !collisions.is_empty() {
+            collisions.sort();
+            collisions.dedup();
+            return Err(TodoRepoError::DuplicateIds(collisions));
+        }
+
+        let mut todos = todos;
+        todos.sort_by_key(|t| t.created_at);
+
+        let imported = todos.len();
+        for todo in todos {
+            self.insert_new(todo);
+        }
+        self.recount();
+
+        Ok(imported)
+    }
+
+    pub fn export_csv(&self) -> String {
+        let mut csv = String::new();
+        write_record(&mut csv, CSV_HEADER);
+        for row in self.to_rows() {
+            write_record(
+                &mut csv,
+                [
+                    row.id.to_string(),
+                    row.text,
+                    row.is_completed.to_string(),
+                    row.created_at,
+                ],
+            );
+        }
+
+        csv
+    }
+
+    pub fn import_csv(&mut self, data: &str) -> Result<usize, ImportError> {
+        let mut records = parse_records(data)?.into_iter().peekable();
+        records.next_if(|(_, fields)| {
+            fields
+                .iter()
+                .map(|field| field.trim().to_lowercase())
+                .eq(CSV_HEADER)
+        });
+
+        let now = SystemTime::now();
+        let mut seen = HashSet::new();
+        let mut todos = Vec::new();
+        for (line, fields) in records {
+            let malformed = |reason| ImportError { line, reason };
+            let [id, text, is_completed, created_at] = <[String; 4]>::try_from(fields)
+                .map_err(|_| malformed("expected id, text, is_completed and created_at"))?;
+
+            let mut todo = Todo::new(text);
+            if todo.text.trim().is_empty() {
+                return Err(malformed("text must not be blank"));
+            }
+            if !id.trim().is_empty() {
+                todo.id = id.trim().parse().map_err(|_| malformed("invalid id"))?;
+            }
+            if !seen.insert(todo.id) || self.items.contains_key(&todo.id) {
+                return Err(malformed("duplicate id"));
+            }
+            if !created_at.trim().is_empty() {
+                todo.created_at = humantime::parse_rfc3339(created_at.trim())
+                    .map_err(|_| malformed("invalid created_at"))?;
+            }
+            let is_completed = match is_completed.trim() {
+                "true" => true,
+                "false" | "" => false,
+                _ => return Err(malformed("is_completed must be true or false")),
+            };
+            todo.set_completed(is_completed, now);
+
+            todos.push(todo);
+        }
+
+        Ok(self.import(todos).expect("ids were checked while parsing"))
+    }
+
+    pub fn to_rows(&self) -> Vec<TodoRow> {
+        self.list(&TodoListFilter::All)
+            .into_iter()
+            .map(|todo| TodoRow {
+                id: todo.id,
+                ref_num: todo.ref_num,
+                is_completed: todo.is_completed,
+                priority: todo.priority,
+                progress: todo.progress,
+                tags: todo.tags.join(","),
+                created_at: format_timestamp(todo.created_at),
+                updated_at: format_timestamp(todo.updated_at),
+                completed_at: todo.completed_at.map(format_timestamp),
+                due_at: todo.due_at.map(format_timestamp),
+                text: todo.text,
+            })
+            .collect()
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = vec![SNAPSHOT_VERSION];
+        bytes.extend(postcard::to_allocvec(&self.snapshot()).expect("todos always serialize"));
+
+        bytes
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<TodoRepo, SnapshotError> {
+        let (version, payload) = bytes.split_first().ok_or(SnapshotError::Malformed)?;
+        if *version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(*version));
+        }
+
+        let snapshot: Snapshot =
+            postcard::from_bytes(payload).map_err(|_| SnapshotError::Malformed)?;
+
+        Ok(Self::from_snapshot(snapshot))
+    }
+
+    pub fn load_from_path(path: &Path) -> io::Result<TodoRepo> {
+        let journal_path = journal_path(path);
+        let file: SnapshotFile = match fs::read(path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && journal_path.exists() => {
+                SnapshotFile::default()
+            }
+            Err(err) => return Err(err),
+        };
+        let mut repo = Self::from_snapshot(file.snapshot);
+        repo.snapshot_generation = file.generation;
+
+        let journal = match fs::read_to_string(&journal_path) {
+            Ok(journal) => journal,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(repo),
+            Err(err) => return Err(err),
+        };
+        for (i, line) in journal.lines().enumerate() {
+            match serde_json::from_str::<JournalEntry>(line) {
+                // Left over from before the last snapshot was written.
+                Ok(entry) if entry.generation != repo.snapshot_generation => {}
+                Ok(JournalEntry {
+                    change: TodoChange::Saved(todo),
+                    ..
+                }) => {
+                    repo.items.insert(todo.id, *todo);
+                }
+                Ok(JournalEntry {
+                    change: TodoChange::Deleted(id),
+                    ..
+                }) => {
+                    repo.items.remove(&id);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "ignoring the rest of {} after malformed line {}: {err}",
+                        journal_path.display(),
+                        i + 1
+                    );
+                    break;
+                }
+            }
+        }
+        repo.recount();
+        let max_ref_num = repo.items.values().map(|t| t.ref_num).max();
+        repo.last_ref_num = repo.last_ref_num.max(max_ref_num.unwrap_or_default());
+
+        Ok(repo)
+    }
+
+    pub fn save_to_path(&mut self, path: &Path) -> io::Result<()> {
+        let file = SnapshotFile {
+            generation: self.snapshot_generation + 1,
+            snapshot: self.snapshot(),
+        };
+        let json = serde_json::to_vec(&file).expect("todos always serialize");
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(tmp_path, path)?;
+        self.snapshot_generation = file.generation;
+
+        match fs::remove_file(journal_path(path)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends the changes since the last call to the journal next to
+    /// `path`, or rewrites the snapshot once the journal outgrows it.
+    pub fn persist_changes(&mut self, path: &Path) -> io::Result<()> {
+        let changes = self.pending_changes();
+        if changes.is_empty() && path.exists() {
+            return Ok(());
+        }
+
+        let journal_path = journal_path(path);
+        let journal_len = fs::metadata(&journal_path).map_or(0, |m| m.len());
+        match fs::metadata(path) {
+            Ok(snapshot) if journal_len <= snapshot.len().max(MIN_JOURNAL_COMPACT_BYTES) => {
+                let mut lines = Vec::new();
+                for change in changes {
+                    let entry = JournalEntry {
+                        generation: self.snapshot_generation,
+                        change,
+                    };
+                    serde_json::to_writer(&mut lines, &entry).expect("todos always serialize");
+                    lines.push(b'\n');
+                }
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&journal_path)?
+                    .write_all(&lines)?;
+            }
+            _ => self.save_to_path(path)?,
+        }
+        self.dirty.clear();
+
+        Ok(())
+    }
+
+    pub fn take_dirty(&mut self) -> Vec<TodoChange> {
+        let changes = self.pending_changes();
+        self.dirty.clear();
+
+        changes
+    }
+
+    pub fn pending_changes(&self) -> Vec<TodoChange> {
+        let mut changes: Vec<_> = self
+            .dirty
+            .iter()
+            .map(|id| match self.items.get(id) {
+                Some(todo) => TodoChange::Saved(Box::new(todo.clone())),
+                None => TodoChange::Deleted(*id),
+            })
+            .collect();
+        changes.sort_by_key(|change| match change {
+            TodoChange::Deleted(id) => (0, 0, *id),
+            TodoChange::Saved(todo) => (1, todo.ref_num, todo.id),
+        });
+
+        changes
+    }
+
+    pub fn repair(&mut self) -> bool {
+        let stored = (
+            self.num_completed_items,
+            self.num_active_items,
+            self.num_all_items,
+            self.last_ref_num,
+        );
+
+        self.recount();
+        let max_ref_num = self.items.values().map(|t| t.ref_num).max();
+        self.last_ref_num = self.last_ref_num.max(max_ref_num.unwrap_or_default());
+
+        let repaired = (
+            self.num_completed_items,
+            self.num_active_items,
+            self.num_all_items,
+            self.last_ref_num,
+        );
+        if stored != repaired {
+            tracing::warn!(
+                "repaired todo counters: (completed, active, all, last_ref_num) \
+                 was {stored:?}, now {repaired:?}"
+            );
+        }
+
+        stored != repaired
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            last_ref_num: self.last_ref_num,
+            num_completed_items: self.num_completed_items,
+            num_active_items: self.num_active_items,
+            num_all_items: self.num_all_items,
+            items: self.export(),
+        }
+    }
+
+    fn from_snapshot(snapshot: Snapshot) -> TodoRepo {
+        let mut repo = TodoRepo {
+            last_ref_num: snapshot.last_ref_num,
+            num_completed_items: snapshot.num_completed_items,
+            num_active_items: snapshot.num_active_items,
+            num_all_items: snapshot.num_all_items,
+            items: snapshot
+                .items
+                .into_iter()
+                .map(|mut t| {
+                    t.updated_at = t.updated_at.max(t.created_at);
+                    if t.order == 0 {
+                        t.order = t.ref_num;
+                    }
+                    (t.id, t)
+                })
+                .collect(),
+            ..TodoRepo::default()
+        };
+        repo.repair();
+
+        repo
+    }
+
+    fn trim_completed(&mut self) {
+        let Some(max) = self.max_completed_retained else {
+            return;
+        };
+
+        let mut completed: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden() && t.is_completed)
+            .map(|t| (t.completed_at, t.ref_num, t.id))
+            .collect();
+        if completed.len() <= max {
+            return;
+        }
+
+        completed.sort();
+        for (_, _, id) in &completed[..completed.len() - max] {
+            self.items.remove(id);
+            self.dirty.insert(*id);
+        }
+    }
+
+    fn recount(&mut self) {
+        let now = SystemTime::now();
+        let counted = || {
+            self.items
+                .values()
+                .filter(|t| !t.is_hidden() && !t.is_expired(now))
+        };
+
+        self.num_all_items = counted().count() as u32;
+        self.num_completed_items = counted().filter(|t| t.is_completed).count() as u32;
+        self.num_active_items = self.num_all_items - self.num_completed_items;
+    }
+
+    /// Archives the visible completed todos, so they stay restorable under
+    /// the `Archived` filter. Only `hard_delete_completed` removes them.
+    pub fn delete_completed(&mut self) {
+        if self.hard_delete_completed {
+            let dirty = &mut self.dirty;
+            self.items.retain(|id, todo| {
+                let keep = todo.is_hidden() || !todo.is_completed;
+                if !keep {
+                    dirty.insert(*id);
+                }
+                keep
+            });
+        } else {
+            let completed: Vec<_> = self
+                .items
+                .values()
+                .filter(|t| !t.is_hidden() && t.is_completed)
+                .map(|t| t.id)
+                .collect();
+            for id in &completed {
+                self.mutate(id, |todo| todo.archived = true)
+                    .expect("todo exists");
+            }
+        }
+        self.recount();
+    }
+
+    pub fn clear(&mut self) {
+        self.dirty.extend(self.items.drain().map(|(id, _)| id));
+        self.recount();
+    }
+
+    pub fn archive(&mut self, id: &Uuid) -> Result<Todo, TodoRepoError> {
+        self.set_archived(id, true)
+    }
+
+    pub fn unarchive(&mut self, id: &Uuid) -> Result<Todo, TodoRepoError> {
+        self.set_archived(id, false)
+    }
+
+    fn set_archived(&mut self, id: &Uuid, archived: bool) -> Result<Todo, TodoRepoError> {
+        let todo = self.mutate(id, |todo| todo.archived = archived)?;
+        self.recount();
+
+        Ok(todo)
+    }
+
+    /// Returns the number of toggled todos and the next occurrences spawned
+    /// by completing recurring ones.
+    pub fn toggle_completed(&mut self, action: &TodoToggleAction) -> (usize, Vec<Todo>) {
+        let is_completed = *action == TodoToggleAction::Check;
+
+        let ids: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| !t.is_hidden() && t.is_completed != is_completed)
+            .map(|t| t.id)
+            .collect();
+        let patch = TodoPatch {
+            is_completed: Some(is_completed),
+            ..TodoPatch::default()
+        };
+        let recurrences = ids
+            .iter()
+            .filter_map(|id| {
+                let (_, next) = self.patch(id, patch.clone(), None).expect("todo exists");
+                next
+            })
+            .collect();
+
+        (ids.len(), recurrences)
+    }
+
+    pub fn toggle_completed_filtered(
+        &mut self,
+        action: &TodoToggleAction,
+        filter: &TodoListFilter,
+    ) -> (usize, Vec<Todo>) {
+        let is_completed = *action == TodoToggleAction::Check;
+        let now = self.now();
+
+        let ids: Vec<_> = self
+            .items
+            .values()
+            .filter(|t| !t.is_expired(now) && filter.matches(t))
+            .filter(|t| t.is_completed != is_completed)
+            .map(|t| t.id)
+            .collect();
+        let patch = TodoPatch {
+            is_completed: Some(is_completed),
+            ..TodoPatch::default()
+        };
+        let recurrences = ids
+            .iter()
+            .filter_map(|id| {
+                let (_, next) = self.patch(id, patch.clone(), None).expect("todo exists");
+                next
+            })
+            .collect();
+
+        (ids.len(), recurrences)
+    }
+}
+
+impl TodoChange {
+    pub fn id(&self) -> Uuid {
+        match self {
+            Self::Saved(todo) => todo.id,
+            Self::Deleted(id) => *id,
+        }
+    }
+}
+
+impl Cursor {
+    pub fn of(todo: &Todo) -> Self {
+        Self {
+            created_at: todo.created_at,
+            id: todo.id,
+        }
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let since_epoch = self
+            .created_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        write!(f, "{}_{}", since_epoch.as_nanos(), self.id.simple())
+    }
+}
+
+impl std::str::FromStr for Cursor {
+    type Err = TodoRepoError;
+
+    fn from_str(cursor: &str) -> Result<Self, Self::Err> {
+        let (nanos, id) = cursor.split_once('_').ok_or(TodoRepoError::InvalidCursor)?;
+        let nanos: u64 = nanos.parse().map_err(|_| TodoRepoError::InvalidCursor)?;
+        let id = Uuid::parse_str(id).map_err(|_| TodoRepoError::InvalidCursor)?;
+
+        Ok(Self {
+            created_at: SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos),
+            id,
+        })
+    }
+}
+
+impl TodoQuery {
+    pub fn new(filter: TodoListFilter) -> Self {
+        Self {
+            filter,
+            text: None,
+            pattern: None,
+            priority: None,
+            tag: None,
+            due_after: None,
+            due_before: None,
+            created_after: None,
+            created_before: None,
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == Self::new(self.filter)
+    }
+}
+
+// Functions
+fn compile_pattern(pattern: &str) -> Result<Regex, TodoRepoError> {
+    if pattern.len() > MAX_PATTERN_LEN {
+        return Err(TodoRepoError::InvalidPattern);
+    }
+
+    RegexBuilder::new(pattern)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .dfa_size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+        .map_err(|_| TodoRepoError::InvalidPattern)
+}
+
+pub fn paginate(todos: Vec<Todo>, offset: usize, limit: usize) -> (Vec<Todo>, usize) {
+    let total = todos.len();
+    let page = todos.into_iter().skip(offset).take(limit).collect();
+
+    (page, total)
+}
+
+pub(crate) fn list_order(todo: &Todo) -> impl Ord + use<> {
+    (
+        Reverse(todo.created_at),
+        Reverse(todo.priority),
+        Reverse(todo.ref_num),
+    )
+}
+
+pub fn diff(before: &[Todo], after: &[Todo]) -> RepoDiff {
+    let before_by_id: HashMap<_, _> = before.iter().map(|t| (t.id, t)).collect();
+    let after_ids: HashSet<_> = after.iter().map(|t| t.id).collect();
+    let mut diff = RepoDiff::default();
+
+    for todo in after {
+        match before_by_id.get(&todo.id) {
+            None => diff.added.push(todo.id),
+            Some(old) => {
+                let fields = changed_fields(old, todo);
+                if !fields.is_empty() {
+                    diff.changed.push((todo.id, fields));
+                }
+            }
+        }
+    }
+
+    diff.removed = before
+        .iter()
+        .map(|t| t.id)
+        .filter(|id| !after_ids.contains(id))
+        .collect();
+
+    diff
+}
+
+fn changed_fields(before: &Todo, after: &Todo) -> Vec<&'static str> {
+    let fields = [
+        ("text", before.text != after.text),
+        ("title", before.title != after.title),
+        ("is_completed", before.is_completed != after.is_completed),
+        ("completed_at", before.completed_at != after.completed_at),
+        ("created_at", before.created_at != after.created_at),
+        ("updated_at", before.updated_at != after.updated_at),
+        ("expires_at", before.expires_at != after.expires_at),
+        ("due_at", before.due_at != after.due_at),
+        ("is_draft", before.is_draft != after.is_draft),
+        ("archived", before.archived != after.archived),
+        ("dismissed_at", before.dismissed_at != after.dismissed_at),
+        ("ref_num", before.ref_num != after.ref_num),
+        ("order", before.order != after.order),
+        ("progress", before.progress != after.progress),
+        ("priority", before.priority != after.priority),
+        ("metadata", before.metadata != after.metadata),
+        ("tags", before.tags != after.tags),
+        ("recurrence", before.recurrence != after.recurrence),
+        ("version", before.version != after.version),
+    ];
+
+    fields
+        .into_iter()
+        .filter_map(|(name, changed)| changed.then_some(name))
+        .collect()
+}
+
+fn journal_path(path: &Path) -> PathBuf {
+    let mut journal = path.as_os_str().to_owned();
+    journal.push(".journal");
+
+    PathBuf::from(journal)
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    }
+}
+
+pub fn parse_timestamp(timestamp: &str) -> Result<SystemTime, TodoRepoError> {
+    humantime::parse_rfc3339(timestamp.trim()).map_err(|_| TodoRepoError::InvalidTimestamp)
+}
+
+fn format_timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+fn format_day(day: i64) -> String {
+    let start_of_day = SystemTime::UNIX_EPOCH + Duration::from_secs((day * DAY_SECS) as u64);
+    let mut formatted = humantime::format_rfc3339_seconds(start_of_day).to_string();
+    formatted.truncate("YYYY-MM-DD".len());
+
+    formatted
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_non_existing_todo() {
+        let repo = TodoRepo::default();
+
+        let result_todo = repo.get(&Uuid::new_v4());
+
+        assert_eq!(result_todo, Err(TodoRepoError::NotFound));
+    }
+
+    #[test]
+    fn test_get_existing_todo() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Teste");
+
+        assert_eq!(Ok(todo.clone()), repo.get(&todo.id));
+    }
+
+    #[test]
+    fn test_get_by_unique_prefix() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        let _other = repo.create("Task B");
+
+        let prefix = todo.id.simple().to_string();
+
+        assert_eq!(Ok(todo.clone()), repo.get_by_prefix(&prefix[..12]));
+        assert_eq!(
+            Ok(todo.clone()),
+            repo.get_by_prefix(&todo.id.to_string().to_uppercase()[..13])
+        );
+    }
+
+    #[test]
+    fn test_get_by_ambiguous_prefix() {
+        let mut repo = TodoRepo::default();
+        let mut todo_a = Todo::new("Task A");
+        let mut todo_b = Todo::new("Task B");
+        todo_a.id = Uuid::parse_str("abcd0000-0000-4000-8000-000000000001").unwrap();
+        todo_b.id = Uuid::parse_str("abcd0000-0000-4000-8000-000000000002").unwrap();
+        repo.items.insert(todo_a.id, todo_a.clone());
+        repo.items.insert(todo_b.id, todo_b);
+
+        assert_eq!(Err(TodoRepoError::Ambiguous), repo.get_by_prefix("abcd"));
+        assert_eq!(
+            Ok(todo_a),
+            repo.get_by_prefix("abcd0000000040008000000000000001")
+        );
+    }
+
+    #[test]
+    fn test_get_by_prefix_no_match() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        let other_prefix = if todo.id.simple().to_string().starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+
+        assert_eq!(
+            Err(TodoRepoError::NotFound),
+            repo.get_by_prefix(other_prefix)
+        );
+        assert_eq!(Err(TodoRepoError::NotFound), repo.get_by_prefix(""));
+    }
+
+    #[test]
+    fn test_list_repo_empty() {
+        let repo = TodoRepo::default();
+        let empty_list: Vec<Todo> = Vec::new();
+
+        let result_completed = repo.list(&TodoListFilter::Completed);
+        let result_active = repo.list(&TodoListFilter::Active);
+        let result_all = repo.list(&TodoListFilter::All);
+
+        assert_eq!(empty_list, result_completed);
+        assert_eq!(empty_list, result_active);
+        assert_eq!(empty_list, result_all);
+    }
+
+    #[test]
+    fn test_list_filled_repo_active() {
+        let mut repo = TodoRepo::default();
+        let mut filled = vec![
+            repo.create("Task A"),
+            repo.create("Task B"),
+            repo.create("Task C"),
+        ];
+
+        filled.reverse();
+
+        assert_eq!(filled, repo.list(&TodoListFilter::Active));
+        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Completed));
+    }
+
+    #[test]
+    fn test_list_filled_repo_complete() {
+        let mut repo = TodoRepo::default();
+        let mut filled = vec![
+            repo.create("Task A"),
+            repo.create("Task B"),
+            repo.create("Task C"),
+        ];
+        filled.reverse();
+
+        for t in filled.iter_mut() {
+            *t = repo.update(&t.id, None, Some(true), None).unwrap();
+            assert!(t.is_completed);
+        }
+
+        assert_eq!(filled, repo.list(&TodoListFilter::Completed));
+        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Active));
+        assert_eq!(
+            repo.list(&TodoListFilter::All),
+            repo.list(&TodoListFilter::Completed)
+        );
+    }
+
+    #[test]
+    fn test_repo_propreties() {
+        let mut repo = TodoRepo::default();
+        let mut todos = vec![repo.create("Task A"), repo.create("Task B")];
+        todos.reverse();
+
+        assert_eq!(todos, repo.list(&TodoListFilter::All));
+        assert_eq!(todos, repo.list(&TodoListFilter::Active));
+        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Completed));
+        assert_eq!(2, repo.num_all_items);
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(2, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_delete_non_existing_todo() {
+        let mut repo = TodoRepo::default();
+
+        let delete_todo = repo.delete(&Uuid::new_v4());
+        assert_eq!(delete_todo, Err(TodoRepoError::NotFound));
+    }
+
+    #[test]
+    fn test_delete_one_todo() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let result = repo.delete(&todo.id);
+        assert_eq!(result, Ok(()));
+        assert_eq!(0, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_update_non_existing_todo() {
+        let mut repo = TodoRepo::default();
+        let result = repo.update(&Uuid::new_v4(), Some("Task A".into()), None, None);
+
+        assert_eq!(result, Err(TodoRepoError::NotFound));
+    }
+
+    #[test]
+    fn test_update_one_existing_todo() {
+        let mut repo = TodoRepo::default();
+        let old_todo = repo.create("Task A");
+        let new_todo = repo
+            .update(&old_todo.id, Some("Task AB".into()), None, None)
+            .unwrap();
+
+        assert_ne!(old_todo, new_todo);
+    }
+
+    #[test]
+    fn test_update_is_completed_true_existing_todo() {
+        let mut repo = TodoRepo::default();
+
+        let todo = repo.create("Task A");
+        let result = repo.update(&todo.id, None, Some(true), None).unwrap();
+
+        assert_eq!(1, repo.num_completed_items);
+        assert_eq!(0, repo.num_active_items);
+        assert_eq!(1, repo.num_all_items);
+        assert!(result.is_completed);
+    }
+
+    #[test]
+    fn test_update_is_completed_false_existing_todo() {
+        let mut repo = TodoRepo::default();
+
+        let todo = repo.create("Task A");
+        let result = repo.update(&todo.id, None, Some(true), None).unwrap();
+        assert!(result.is_completed);
+
+        let result = repo.update(&todo.id, None, Some(false), None).unwrap();
+
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(1, repo.num_active_items);
+        assert_eq!(1, repo.num_all_items);
+        assert!(!result.is_completed);
+    }
+
+    #[test]
+    fn test_delete_completed_todos() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let task_c = repo.create("Task C");
+
+        let _task_a = repo.update(&task_a.id, None, Some(true), None).unwrap();
+        let _task_c = repo.update(&task_c.id, None, Some(true), None).unwrap();
+
+        repo.delete_completed();
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.num_active_items);
+        assert_eq!(vec![task_b.clone()], repo.list(&TodoListFilter::All));
+    }
+
+    #[test]
+    fn test_delete_completed_archives_by_default() {
+        let mut repo = TodoRepo::default();
+        let done = repo.create("Task A");
+        let open = repo.create("Task B");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+
+        repo.delete_completed();
+
+        let archived = repo.get(&done.id).unwrap();
+        assert!(archived.archived);
+        assert_eq!(vec![archived], repo.list(&TodoListFilter::Archived));
+        assert!(repo.list(&TodoListFilter::Completed).is_empty());
+        assert_eq!(
+            vec![open.id],
+            repo.list(&TodoListFilter::All)
+                .iter()
+                .map(|t| t.id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            (0, 1, 1),
+            (
+                repo.num_completed_items,
+                repo.num_active_items,
+                repo.num_all_items
+            )
+        );
+    }
+
+    #[test]
+    fn test_hard_delete_completed_drops_todos() {
+        let mut repo = TodoRepo {
+            hard_delete_completed: true,
+            ..TodoRepo::default()
+        };
+        let done = repo.create("Task A");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+
+        repo.delete_completed();
+
+        assert_eq!(Err(TodoRepoError::NotFound), repo.get(&done.id));
+        assert!(repo.list(&TodoListFilter::Archived).is_empty());
+    }
+
+    #[test]
+    fn test_archive_and_unarchive() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let archived = repo.archive(&todo.id).unwrap();
+        assert!(archived.archived);
+        assert_eq!(0, repo.num_all_items);
+        assert!(repo.list(&TodoListFilter::Active).is_empty());
+
+        let restored = repo.unarchive(&todo.id).unwrap();
+        assert!(!restored.archived);
+        assert_eq!(1, repo.num_active_items);
+        assert!(repo.list(&TodoListFilter::Archived).is_empty());
+        assert_eq!(Err(TodoRepoError::NotFound), repo.archive(&Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_list_matching_pattern() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Write report");
+        let _task_b = repo.create("Buy milk");
+        let task_c = repo.create("Review report draft");
+        let _ = repo.update(&task_c.id, None, Some(true), None);
+
+        let all = repo.list_matching(&TodoListFilter::All, "(?i)^(write|review) report");
+        let active = repo.list_matching(&TodoListFilter::Active, "report");
+
+        assert_eq!(
+            vec![task_c.id, task_a.id],
+            all.unwrap().iter().map(|t| t.id).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![task_a], active.unwrap());
+    }
+
+    #[test]
+    fn test_list_matching_invalid_pattern() {
+        let mut repo = TodoRepo::default();
+        repo.create("Task A");
+
+        let unbalanced = repo.list_matching(&TodoListFilter::All, "(Task");
+        let too_long = repo.list_matching(&TodoListFilter::All, &"a".repeat(MAX_PATTERN_LEN + 1));
+
+        assert_eq!(unbalanced, Err(TodoRepoError::InvalidPattern));
+        assert_eq!(too_long, Err(TodoRepoError::InvalidPattern));
+    }
+
+    #[test]
+    fn test_set_and_get_metadata() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let updated = repo.set_metadata(&todo.id, "ticket", "JIRA-1").unwrap();
+
+        assert_eq!(Some(&"JIRA-1".to_string()), updated.metadata.get("ticket"));
+        assert_eq!(
+            Ok("JIRA-1".to_string()),
+            repo.get_metadata(&todo.id, "ticket")
+        );
+        assert_eq!(
+            Err(TodoRepoError::MetadataKeyNotFound),
+            repo.get_metadata(&todo.id, "other")
+        );
+    }
+
+    #[test]
+    fn test_overwrite_metadata() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        repo.set_metadata(&todo.id, "ticket", "JIRA-1").unwrap();
+        let updated = repo.set_metadata(&todo.id, "ticket", "JIRA-2").unwrap();
+
+        assert_eq!(1, updated.metadata.len());
+        assert_eq!(
+            Ok("JIRA-2".to_string()),
+            repo.get_metadata(&todo.id, "ticket")
+        );
+    }
+
+    #[test]
+    fn test_delete_metadata() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        repo.set_metadata(&todo.id, "ticket", "JIRA-1").unwrap();
+
+        let updated = repo.delete_metadata(&todo.id, "ticket").unwrap();
+
+        assert!(updated.metadata.is_empty());
+        assert_eq!(
+            Err(TodoRepoError::MetadataKeyNotFound),
+            repo.delete_metadata(&todo.id, "ticket")
+        );
+    }
+
+    #[test]
+    fn test_metadata_limits() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        for i in 0..MAX_METADATA_KEYS {
+            repo.set_metadata(&todo.id, format!("key-{i}"), "value")
+                .unwrap();
+        }
+
+        let too_many = repo.set_metadata(&todo.id, "one-more", "value");
+        let too_long = repo.set_metadata(&todo.id, "key-0", "a".repeat(MAX_METADATA_VALUE_LEN + 1));
+        let overwrite = repo.set_metadata(&todo.id, "key-0", "new value");
+
+        assert_eq!(Err(TodoRepoError::MetadataTooLarge), too_many);
+        assert_eq!(Err(TodoRepoError::MetadataTooLarge), too_long);
+        assert!(overwrite.is_ok());
+    }
+
+    #[test]
+    fn test_expired_todo_excluded_from_list() {
+        let mut repo = TodoRepo::default();
+        let expired = repo.create("Task A");
+        let kept = repo.create("Task B");
+        let past = SystemTime::now() - Duration::from_secs(60);
+        let future = SystemTime::now() + Duration::from_secs(3600);
+
+        repo.set_expiry(&expired.id, Some(past)).unwrap();
+        let kept = repo.set_expiry(&kept.id, Some(future)).unwrap();
+
+        assert_eq!(vec![kept], repo.list(&TodoListFilter::All));
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_delete_expired_todos() {
+        let mut repo = TodoRepo::default();
+        let expired = repo.create("Task A");
+        let kept = repo.create("Task B");
+        let cleared = repo.create("Task C");
+        let now = SystemTime::now();
+
+        repo.set_expiry(&expired.id, Some(now - Duration::from_secs(1)))
+            .unwrap();
+        repo.set_expiry(&kept.id, Some(now + Duration::from_secs(3600)))
+            .unwrap();
+        repo.set_expiry(&cleared.id, Some(now - Duration::from_secs(1)))
+            .unwrap();
+        repo.set_expiry(&cleared.id, None).unwrap();
+
+        assert_eq!(1, repo.delete_expired(now));
+        assert_eq!(Err(TodoRepoError::NotFound), repo.get(&expired.id));
+        assert!(repo.get(&kept.id).is_ok());
+        assert!(repo.get(&cleared.id).is_ok());
+        assert_eq!(2, repo.num_all_items);
+        assert_eq!(2, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_validate_text() {
+        let repo = TodoRepo::default();
+
+        assert_eq!(Ok(()), repo.validate_text("Buy milk", DEFAULT_MAX_TEXT_LEN));
+        assert_eq!(
+            Err(TodoRepoError::InvalidInput),
+            repo.validate_text("", DEFAULT_MAX_TEXT_LEN)
+        );
+        assert_eq!(
+            Err(TodoRepoError::InvalidInput),
+            repo.validate_text(" \t\n", DEFAULT_MAX_TEXT_LEN)
+        );
+        assert_eq!(
+            Err(TodoRepoError::TextTooLong { max: 1000 }),
+            repo.validate_text(&"x".repeat(1001), 1000)
+        );
+    }
+
+    #[test]
+    fn test_validate_text_counts_chars_not_bytes() {
+        let repo = TodoRepo::default();
+        let at_limit = "🦀".repeat(DEFAULT_MAX_TEXT_LEN);
+
+        assert_eq!(Ok(()), repo.validate_text(&at_limit, DEFAULT_MAX_TEXT_LEN));
+        assert_eq!(
+            Err(TodoRepoError::TextTooLong {
+                max: DEFAULT_MAX_TEXT_LEN
+            }),
+            repo.validate_text(&format!("{at_limit}🦀"), DEFAULT_MAX_TEXT_LEN)
+        );
+    }
+
+    #[test]
+    fn test_ensure_unique_text() {
+        let mut repo = TodoRepo::default();
+        let milk = repo.create("Buy milk");
+
+        assert_eq!(
+            Err(TodoRepoError::DuplicateText),
+            repo.ensure_unique_text("  buy   MILK ", None)
+        );
+        assert_eq!(Ok(()), repo.ensure_unique_text("Buy milk", Some(&milk.id)));
+        assert_eq!(Ok(()), repo.ensure_unique_text("Buy eggs", None));
+    }
+
+    #[test]
+    fn test_ensure_unique_texts_checks_within_batch() {
+        let mut repo = TodoRepo::default();
+        repo.create("Buy milk");
+
+        assert_eq!(Ok(()), repo.ensure_unique_texts(&["Buy eggs", "Buy bread"]));
+        assert_eq!(
+            Err(TodoRepoError::DuplicateText),
+            repo.ensure_unique_texts(&["Buy eggs", " buy MILK"])
+        );
+        assert_eq!(
+            Err(TodoRepoError::DuplicateText),
+            repo.ensure_unique_texts(&["a", "A"])
+        );
+    }
+
+    #[test]
+    fn test_ensure_unique_text_ignores_hidden_todos() {
+        let mut repo = TodoRepo::default();
+        let milk = repo.create("Buy milk");
+        let eggs = repo.create("Buy eggs");
+        repo.create_draft("Buy bread");
+
+        repo.update(&milk.id, None, Some(true), None).unwrap();
+        repo.delete_completed();
+        repo.dismiss(&eggs.id).unwrap();
+
+        assert_eq!(Ok(()), repo.ensure_unique_text("Buy milk", None));
+        assert_eq!(Ok(()), repo.ensure_unique_text("Buy eggs", None));
+        assert_eq!(Ok(()), repo.ensure_unique_text("Buy bread", None));
+
+        repo.unarchive(&milk.id).unwrap();
+        assert_eq!(
+            Err(TodoRepoError::DuplicateText),
+            repo.ensure_unique_text("Buy milk", None)
+        );
+    }
+
+    #[test]
+    fn test_approx_size_counts_owned_data() {
+        let mut repo = TodoRepo::default();
+        assert_eq!(0, repo.approx_size_bytes());
+
+        let todo = repo.create("Task A");
+        let base = repo.approx_size_bytes();
+        assert_eq!(size_of::<Uuid>() + size_of::<Todo>() + 6, base);
+
+        repo.update(
+            &todo.id,
+            Some("Task A with a longer text".into()),
+            None,
+            None,
+        )
+        .unwrap();
+        let longer = repo.approx_size_bytes();
+        assert_eq!(base + 19, longer);
+
+        repo.set_metadata(&todo.id, "ticket", "JIRA-1").unwrap();
+        let with_metadata = repo.approx_size_bytes();
+        assert_eq!(longer + 2 * size_of::<String>() + 12, with_metadata);
+
+        repo.set_tags(&todo.id, vec!["home".into(), "errands".into()])
+            .unwrap();
+        let with_tags = repo.approx_size_bytes();
+        assert_eq!(with_metadata + 2 * size_of::<String>() + 11, with_tags);
+
+        repo.items.get_mut(&todo.id).unwrap().title = Some("Task".into());
+        assert_eq!(with_tags + 4, repo.approx_size_bytes());
+    }
+
+    #[test]
+    fn test_create_draft_not_counted() {
+        let mut repo = TodoRepo::default();
+        let active = repo.create("Task A");
+        let draft = repo.create_draft("Task B");
+
+        assert!(draft.is_draft);
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.num_active_items);
+        assert_eq!(vec![active], repo.list(&TodoListFilter::All));
+        assert_eq!(vec![draft], repo.list(&TodoListFilter::Drafts));
+    }
+
+    #[test]
+    fn test_draft_ignored_by_bulk_actions() {
+        let mut repo = TodoRepo::default();
+        let draft = repo.create_draft("Task A");
+        repo.update(&draft.id, None, Some(true), None).unwrap();
+        repo.create("Task B");
+
+        repo.toggle_completed(&TodoToggleAction::Uncheck);
+        repo.toggle_completed(&TodoToggleAction::Check);
+        repo.delete_completed();
+
+        assert!(repo.get(&draft.id).unwrap().is_completed);
+        assert_eq!(0, repo.num_all_items);
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(Ok(()), repo.delete(&draft.id));
+        assert_eq!(0, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_publish_draft() {
+        let mut repo = TodoRepo::default();
+        let draft = repo.create_draft("Task A");
+
+        let published = repo.publish(&draft.id).unwrap();
+        let republished = repo.publish(&draft.id).unwrap();
+
+        assert!(!published.is_draft);
+        assert_eq!(published, republished);
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.num_active_items);
+        assert_eq!(vec![published], repo.list(&TodoListFilter::Active));
+        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Drafts));
+        assert_eq!(Err(TodoRepoError::NotFound), repo.publish(&Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_dismiss_hides_without_completing() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        repo.update(&task_b.id, None, Some(true), None).unwrap();
+
+        let dismissed = repo.dismiss(&task_a.id).unwrap();
+
+        assert!(!dismissed.is_completed);
+        assert!(dismissed.dismissed_at.is_some());
+        assert_eq!(1, repo.num_completed_items);
+        assert_eq!(0, repo.num_active_items);
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(vec![dismissed], repo.list(&TodoListFilter::Dismissed));
+        assert_eq!(
+            vec![task_b.id],
+            repo.list(&TodoListFilter::All)
+                .into_iter()
+                .map(|t| t.id)
+                .collect::<Vec<_>>()
+        );
+
+        repo.toggle_completed(&TodoToggleAction::Check);
+        assert!(!repo.get(&task_a.id).unwrap().is_completed);
+    }
+
+    #[test]
+    fn test_restore_dismissed_todo() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        repo.dismiss(&todo.id).unwrap();
+
+        let restored = repo.restore(&todo.id).unwrap();
+
+        assert_eq!(None, restored.dismissed_at);
+        assert_eq!(1, repo.num_active_items);
+        assert_eq!(vec![restored], repo.list(&TodoListFilter::Active));
+        assert!(repo.list(&TodoListFilter::Dismissed).is_empty());
+        assert_eq!(Err(TodoRepoError::NotFound), repo.restore(&Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_restore_deleted_todo() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        repo.update(&todo.id, None, Some(true), None).unwrap();
+        repo.delete(&todo.id).unwrap();
+        assert_eq!(0, repo.num_all_items);
+
+        let restored = repo.restore(&todo.id).unwrap();
+
+        assert_eq!(todo.ref_num, restored.ref_num);
+        assert!(restored.is_completed);
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.num_completed_items);
+        assert!(repo.recently_deleted.is_empty());
+        assert_eq!(Ok(restored), repo.get(&todo.id));
+    }
+
+    #[test]
+    fn test_restore_after_ttl_is_not_found() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        repo.delete(&todo.id).unwrap();
+        let expired = Instant::now()
+            .checked_sub(RESTORE_TTL + Duration::from_secs(1))
+            .unwrap();
+        repo.recently_deleted[0].0 = expired;
+
+        assert_eq!(Err(TodoRepoError::NotFound), repo.restore(&todo.id));
+        assert!(repo.recently_deleted.is_empty());
+        assert_eq!(Err(TodoRepoError::NotFound), repo.restore(&Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_partition_by_completion() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let task_c = repo.create("Task C");
+        let task_a = repo.update(&task_a.id, None, Some(true), None).unwrap();
+        let task_c = repo.update(&task_c.id, None, Some(true), None).unwrap();
+        repo.create_draft("Draft");
+
+        let (completed, active) = repo.partition(|t| t.is_completed);
+
+        assert_eq!(vec![task_c, task_a], completed);
+        assert_eq!(vec![task_b], active);
+    }
+
+    #[test]
+    fn test_partition_by_text() {
+        let mut repo = TodoRepo::default();
+        let report = repo.create("Write report");
+        let milk = repo.create("Buy milk");
+        let review = repo.create("Review report");
+
+        let (matching, rest) = repo.partition(|t| t.text.contains("report"));
+
+        assert_eq!(vec![review, report], matching);
+        assert_eq!(vec![milk], rest);
+        assert_eq!(repo.list(&TodoListFilter::All).len(), 3);
+    }
+
+    #[test]
+    fn test_toggle_completed_counts_changed_todos() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        repo.create("Task B");
+        repo.create("Task C");
+        repo.create_draft("Draft");
+        repo.update(&task_a.id, None, Some(true), None).unwrap();
+
+        assert_eq!(2, repo.toggle_completed(&TodoToggleAction::Check).0);
+        assert_eq!(0, repo.toggle_completed(&TodoToggleAction::Check).0);
+        assert_eq!(3, repo.num_completed_items);
+        assert_eq!(0, repo.num_active_items);
+
+        assert_eq!(3, repo.toggle_completed(&TodoToggleAction::Uncheck).0);
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(3, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_toggle_completed_recurs_like_single_toggle() {
+        let mut repo = TodoRepo::default();
+        let chore = repo.create("Water plants");
+        repo.set_recurrence(&chore.id, Some(Duration::from_secs(3600)))
+            .unwrap();
+        let errand = repo.create("Buy milk");
+        repo.create("Call mom");
+
+        let (num_changed, recurrences) = repo.toggle_completed(&TodoToggleAction::Check);
+        assert_eq!(3, num_changed);
+        assert_eq!(1, recurrences.len());
+        let next = &recurrences[0];
+        assert_eq!("Water plants", next.text);
+        assert!(!repo.get(&next.id).unwrap().is_completed);
+        assert!(next.due_at.is_some());
+        assert_eq!(1, repo.num_active_items);
+
+        let (_, recurrences) = repo.toggle_completed(&TodoToggleAction::Uncheck);
+        assert!(recurrences.is_empty());
+        assert_eq!(4, repo.num_all_items);
+
+        repo.update(&errand.id, None, Some(true), None).unwrap();
+        let (num_changed, recurrences) =
+            repo.toggle_completed_filtered(&TodoToggleAction::Check, &TodoListFilter::Active);
+        assert_eq!(3, num_changed);
+        assert_eq!(2, recurrences.len());
+        assert_eq!(6, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_toggle_completed_filtered() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let draft = repo.create_draft("Draft");
+        repo.update(&task_a.id, None, Some(true), None).unwrap();
+
+        repo.toggle_completed_filtered(&TodoToggleAction::Uncheck, &TodoListFilter::Active);
+        assert!(repo.get(&task_a.id).unwrap().is_completed);
+
+        repo.toggle_completed_filtered(&TodoToggleAction::Uncheck, &TodoListFilter::Completed);
+        assert!(!repo.get(&task_a.id).unwrap().is_completed);
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(2, repo.num_active_items);
+
+        repo.toggle_completed_filtered(&TodoToggleAction::Check, &TodoListFilter::Drafts);
+        assert!(repo.get(&draft.id).unwrap().is_completed);
+        assert!(!repo.get(&task_b.id).unwrap().is_completed);
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(2, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_toggle_completed_filtered_by_each_filter() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let overdue = repo.create("Overdue");
+        repo.set_due(&overdue.id, Some(SystemTime::UNIX_EPOCH))
+            .unwrap();
+        let dismissed = repo.create("Dismissed");
+        repo.dismiss(&dismissed.id).unwrap();
+        let archived = repo.create("Archived");
+        repo.update(&archived.id, None, Some(true), None).unwrap();
+        repo.archive(&archived.id).unwrap();
+
+        let is_completed = |repo: &TodoRepo, id: &Uuid| repo.get(id).unwrap().is_completed;
+
+        assert_eq!(
+            1,
+            repo.toggle_completed_filtered(&TodoToggleAction::Check, &TodoListFilter::Overdue)
+                .0
+        );
+        assert!(is_completed(&repo, &overdue.id));
+        assert!(!is_completed(&repo, &task_a.id));
+        assert_eq!(1, repo.num_completed_items);
+
+        assert_eq!(
+            1,
+            repo.toggle_completed_filtered(&TodoToggleAction::Check, &TodoListFilter::Dismissed)
+                .0
+        );
+        assert!(is_completed(&repo, &dismissed.id));
+        assert_eq!(1, repo.num_completed_items);
+
+        assert_eq!(
+            1,
+            repo.toggle_completed_filtered(&TodoToggleAction::Uncheck, &TodoListFilter::Archived)
+                .0
+        );
+        assert!(!is_completed(&repo, &archived.id));
+
+        assert_eq!(
+            1,
+            repo.toggle_completed_filtered(&TodoToggleAction::Check, &TodoListFilter::Manual)
+                .0
+        );
+        assert!(is_completed(&repo, &task_a.id));
+        assert_eq!(2, repo.num_completed_items);
+
+        assert_eq!(
+            2,
+            repo.toggle_completed_filtered(&TodoToggleAction::Uncheck, &TodoListFilter::All)
+                .0
+        );
+        assert!(is_completed(&repo, &dismissed.id));
+        assert_eq!(0, repo.num_completed_items);
+        assert_eq!(2, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_ref_nums_increase_and_are_not_reused() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let draft = repo.create_draft("Draft");
+
+        repo.delete(&task_b.id).unwrap();
+        let task_c = repo.create("Task C");
+
+        assert_eq!(
+            vec![1, 2, 3, 4],
+            vec![
+                task_a.ref_num,
+                task_b.ref_num,
+                draft.ref_num,
+                task_c.ref_num
+            ]
+        );
+        assert_eq!(Ok(task_a), repo.get_by_ref(1));
+        assert_eq!(Ok(task_c), repo.get_by_ref(4));
+        assert_eq!(Err(TodoRepoError::NotFound), repo.get_by_ref(2));
+    }
+
+    #[test]
+    fn test_import_exported_todos() {
+        let mut source = TodoRepo::default();
+        let task_a = source.create("Task A");
+        source.create("Task B");
+        source.create_draft("Draft");
+        source.update(&task_a.id, None, Some(true), None).unwrap();
+
+        let mut target = TodoRepo::default();
+        target.create("Existing");
+        let imported = target.import(source.export()).unwrap();
+
+        assert_eq!(3, imported);
+        assert_eq!(3, target.num_all_items);
+        assert_eq!(1, target.num_completed_items);
+        assert_eq!(2, target.num_active_items);
+        assert_eq!(2, target.get(&task_a.id).unwrap().ref_num);
+        assert_eq!(4, target.last_ref_num);
+    }
+
+    #[test]
+    fn test_age_distribution_of_active_todos() {
+        let mut repo = TodoRepo::default();
+        let now = SystemTime::now();
+        for (text, age_days) in [
+            ("A", 0),
+            ("B", 3),
+            ("C", 6),
+            ("D", 10),
+            ("E", 45),
+            ("F", 45),
+        ] {
+            let mut todo = Todo::new(text);
+            todo.created_at = now - DAY * age_days;
+            repo.import(vec![todo]).unwrap();
+        }
+        let done = repo.create("Done");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+
+        assert_eq!(
+            vec![("<1d", 1), ("1-7d", 2), ("7-30d", 1), ("30d+", 2)],
+            repo.age_distribution(now)
+        );
+    }
+
+    #[test]
+    fn test_truncated_timestamps_survive_serialization() {
+        let mut repo = TodoRepo {
+            truncate_timestamps: true,
+            ..TodoRepo::default()
+        };
+        let todo = repo.create("Task A");
+        let todo = repo
+            .set_expiry(&todo.id, Some(SystemTime::now() + DAY))
+            .unwrap();
+
+        let json = serde_json::to_string(&todo).unwrap();
+        let decoded: Todo = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(todo, decoded);
+        assert_eq!(todo.created_at, truncate_to_millis(todo.created_at));
+        assert_eq!(todo.expires_at, todo.expires_at.map(truncate_to_millis));
+    }
+
+    #[test]
+    fn test_completed_at_follows_completion() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        assert_eq!(None, todo.completed_at);
+
+        let completed = repo.update(&todo.id, None, Some(true), None).unwrap();
+        let still_completed = repo.update(&todo.id, None, Some(true), None).unwrap();
+        let reopened = repo.update(&todo.id, None, Some(false), None).unwrap();
+
+        assert!(completed.completed_at.is_some());
+        assert_eq!(completed.completed_at, still_completed.completed_at);
+        assert_eq!(None, reopened.completed_at);
+    }
+
+    #[test]
+    fn test_forecast_completion_from_velocity() {
+        let mut repo = TodoRepo::default();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for days_ago in 1..=7 {
+            let mut todo = Todo::new("Done");
+            todo.set_completed(true, now - DAY * days_ago * 2);
+            repo.import(vec![todo]).unwrap();
+        }
+        for _ in 0..3 {
+            repo.create("Open");
+        }
+
+        assert_eq!(Some(now + DAY * 6), repo.forecast_completion(now, 0));
+    }
+
+    #[test]
+    fn test_forecast_completion_without_velocity() {
+        let mut repo = TodoRepo::default();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        repo.create("Open");
+
+        let mut stale = Todo::new("Done long ago");
+        stale.set_completed(true, now - DAY * 60);
+        repo.import(vec![stale]).unwrap();
+
+        assert_eq!(None, repo.forecast_completion(now, 0));
+    }
+
+    #[test]
+    fn test_stats_totals() {
+        let mut repo = TodoRepo::default();
+        let now = SystemTime::now();
+        let task_a = repo.create("Task A");
+        repo.create("Task B");
+        repo.update(&task_a.id, None, Some(true), None).unwrap();
+
+        let mut old = Todo::new("Task C");
+        old.created_at = now - DAY * 2;
+        repo.import(vec![old]).unwrap();
+        repo.create("Task D");
+
+        assert_eq!(
+            TodoStats {
+                num_all: 4,
+                num_active: 3,
+                num_completed: 1,
+                completion_percentage: 25.0,
+                created_last_24h: 3,
+            },
+            repo.stats(now + Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn test_stats_empty_repo_has_zero_percentage() {
+        let stats = TodoRepo::default().stats(SystemTime::now());
+
+        assert_eq!(0.0, stats.completion_percentage);
+        assert_eq!(0, stats.num_all);
+    }
+
+    #[test]
+    fn test_completed_todos_are_trimmed_to_cap() {
+        let mut repo = TodoRepo {
+            max_completed_retained: Some(2),
+            ..TodoRepo::default()
+        };
+        let done: Vec<_> = ["Done A", "Done B", "Done C"]
+            .into_iter()
+            .map(|text| repo.create(text))
+            .collect();
+        let open = repo.create("Open");
+
+        for todo in &done {
+            repo.update(&todo.id, None, Some(true), None).unwrap();
+        }
+
+        assert_eq!(Err(TodoRepoError::NotFound), repo.get(&done[0].id));
+        assert!(repo.get(&done[1].id).unwrap().is_completed);
+        assert!(repo.get(&done[2].id).unwrap().is_completed);
+        assert!(!repo.get(&open.id).unwrap().is_completed);
+        assert_eq!(2, repo.num_completed_items);
+        assert_eq!(1, repo.num_active_items);
+
+        repo.toggle_completed(&TodoToggleAction::Check);
+        assert_eq!(2, repo.num_completed_items);
+        assert_eq!(2, repo.num_all_items);
+        assert!(repo.get(&open.id).unwrap().is_completed);
+    }
+
+    #[test]
+    fn test_burndown_series() {
+        let mut repo = TodoRepo::default();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let history = [
+            ("A", 3, None),
+            ("B", 3, Some(2)),
+            ("C", 2, Some(0)),
+            ("D", 1, None),
+            ("E", 0, None),
+        ];
+        for (text, created_days_ago, completed_days_ago) in history {
+            let mut todo = Todo::new(text);
+            todo.created_at = now - DAY * created_days_ago;
+            if let Some(days_ago) = completed_days_ago {
+                todo.set_completed(true, now - DAY * days_ago);
+            }
+            repo.import(vec![todo]).unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                ("2023-11-11".to_string(), 2),
+                ("2023-11-12".to_string(), 2),
+                ("2023-11-13".to_string(), 3),
+                ("2023-11-14".to_string(), 3),
+            ],
+            repo.burndown(now, 4, 0)
+        );
+        assert_eq!(
+            vec![("2023-11-15".to_string(), 3)],
+            repo.burndown(now, 1, 14 * 60)
+        );
+    }
+
+    #[test]
+    fn test_completions_by_hour() {
+        let mut repo = TodoRepo::default();
+        let midnight = SystemTime::UNIX_EPOCH + DAY * 19_675;
+        let hour = Duration::from_secs(60 * 60);
+        for (text, completed_at) in [
+            ("A", Some(midnight + hour * 9)),
+            (
+                "B",
+                Some(midnight + hour * 9 + Duration::from_secs(59 * 60)),
+            ),
+            ("C", Some(midnight + hour * 23)),
+            ("D", None),
+        ] {
+            let mut todo = Todo::new(text);
+            if let Some(completed_at) = completed_at {
+                todo.set_completed(true, completed_at);
+            }
+            repo.import(vec![todo]).unwrap();
+        }
+
+        let utc = repo.completions_by_hour(0);
+        assert_eq!(2, utc[9]);
+        assert_eq!(1, utc[23]);
+        assert_eq!(3, utc.iter().sum::<u32>());
+
+        let shifted = repo.completions_by_hour(-10 * 60);
+        assert_eq!(2, shifted[23]);
+        assert_eq!(1, shifted[13]);
+        assert_eq!(3, shifted.iter().sum::<u32>());
+    }
+
+    #[test]
+    fn test_auto_title_from_long_text() {
+        let mut repo = TodoRepo {
+            auto_title_len: Some(20),
+            ..TodoRepo::default()
+        };
+
+        let short = repo.create("Buy milk");
+        let long = repo.create("Draft the quarterly report and send it to finance");
+
+        assert_eq!(Some("Buy milk"), short.title.as_deref());
+        assert_eq!(Some("Draft the quarterly…"), long.title.as_deref());
+        assert_eq!(
+            "Draft the quarterly report and send it to finance",
+            long.text
+        );
+        assert_eq!("Draft the quarterly…", long.display_text());
+
+        let updated = repo
+            .update(
+                &short.id,
+                Some("Buy milk, eggs and a loaf of bread".into()),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(Some("Buy milk, eggs and a…"), updated.title.as_deref());
+    }
+
+    #[test]
+    fn test_no_title_without_auto_title() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Draft the quarterly report and send it to finance");
+
+        assert_eq!(None, todo.title);
+        assert_eq!(todo.text, todo.display_text());
+    }
+
+    #[test]
+    fn test_import_rejects_duplicate_ids_in_payload() {
+        let todo = Todo::new("Task A");
+        let payload = vec![todo.clone(), Todo::new("Task B"), todo.clone()];
+
+        let mut repo = TodoRepo::default();
+
+        assert_eq!(
+            Err(TodoRepoError::DuplicateIds(vec![todo.id])),
+            repo.import(payload)
+        );
+        assert!(repo.items.is_empty());
+        assert_eq!(0, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_import_rejects_ids_colliding_with_existing() {
+        let mut repo = TodoRepo::default();
+        let existing = repo.create("Existing");
+        let mut clash = Todo::new("Imported");
+        clash.id = existing.id;
+
+        assert_eq!(
+            Err(TodoRepoError::DuplicateIds(vec![existing.id])),
+            repo.import(vec![Todo::new("Task B"), clash])
+        );
+        assert_eq!("Existing", repo.get(&existing.id).unwrap().text);
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.last_ref_num);
+    }
+
+    #[test]
+    fn test_lint_flags_suspicious_todos() {
+        let mut repo = TodoRepo::default();
+        let clean = repo.create("Buy milk");
+        let blank = repo.create("   ");
+        let long = repo.create("x".repeat(LINT_MAX_TEXT_LEN + 1));
+        let overdue = repo.create("Renew passport");
+        repo.set_due(
+            &overdue.id,
+            Some(SystemTime::now() - LINT_MAX_OVERDUE - DAY),
+        )
+        .unwrap();
+        let recently_due = repo.create("Water plants");
+        repo.set_due(&recently_due.id, Some(SystemTime::now() - DAY))
+            .unwrap();
+
+        let warnings = repo.lint();
+
+        assert_eq!(
+            vec![
+                (blank.id, vec![LintWarning::BlankText]),
+                (long.id, vec![LintWarning::TextTooLong]),
+                (overdue.id, vec![LintWarning::LongOverdue]),
+            ],
+            warnings
+        );
+        assert!(warnings.iter().all(|(id, _)| *id != clean.id));
+    }
+
+    #[test]
+    fn test_set_partial_progress() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let todo = repo.set_progress(&todo.id, 50).unwrap();
+
+        assert_eq!(50, todo.progress);
+        assert!(!todo.is_completed);
+        assert_eq!(0, repo.num_completed_items);
+    }
+
+    #[test]
+    fn test_full_progress_completes_todo() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let todo = repo.set_progress(&todo.id, 100).unwrap();
+        assert!(todo.is_completed);
+        assert_eq!(1, repo.num_completed_items);
+
+        let todo = repo.set_progress(&todo.id, 80).unwrap();
+        assert!(!todo.is_completed);
+        assert_eq!(80, todo.progress);
+        assert_eq!(0, repo.num_completed_items);
+
+        let todo = repo.update(&todo.id, None, Some(true), None).unwrap();
+        assert_eq!(100, todo.progress);
+    }
+
+    #[test]
+    fn test_progress_out_of_range_rejected() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        assert_eq!(
+            Err(TodoRepoError::InvalidProgress),
+            repo.set_progress(&todo.id, 101)
+        );
+        assert_eq!(0, repo.get(&todo.id).unwrap().progress);
+    }
+
+    #[test]
+    fn test_create_with_priority() {
+        let mut repo = TodoRepo::default();
+
+        let plain = repo.create("Task A");
+        let urgent = repo.create_with_priority("Task B", Some(Priority::High));
+
+        assert_eq!(Priority::Medium, plain.priority);
+        assert_eq!(Priority::High, urgent.priority);
+        assert_eq!(2, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_list_paged() {
+        let mut repo = TodoRepo::default();
+        for text in ["Task A", "Task B", "Task C", "Task D"] {
+            repo.create(text);
+        }
+        let done = repo.create("Task E");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+
+        let (page, total) = repo.list_paged(&TodoListFilter::Active, 1, 2);
+        let texts: Vec<_> = page.into_iter().map(|t| t.text).collect();
+
+        assert_eq!(vec!["Task C", "Task B"], texts);
+        assert_eq!(4, total);
+        assert_eq!(
+            (Vec::new(), 4),
+            repo.list_paged(&TodoListFilter::Active, 10, 2)
+        );
+        assert_eq!(1, repo.list_paged(&TodoListFilter::Completed, 0, 50).1);
+    }
+
+    #[test]
+    fn test_create_unique_returns_active_duplicate() {
+        let mut repo = TodoRepo::default();
+        let (milk, created) = repo.create_unique("Buy milk");
+        assert!(created);
+
+        let (again, created) = repo.create_unique("  BUY Milk ");
+        assert!(!created);
+        assert_eq!(milk, again);
+        assert_eq!(1, repo.num_all_items);
+
+        repo.update(&milk.id, None, Some(true), None).unwrap();
+        let (fresh, created) = repo.create_unique("Buy milk");
+        assert!(created);
+        assert_ne!(milk.id, fresh.id);
+        assert_eq!(2, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_create_unique_skips_expired_duplicate() {
+        let mut repo = TodoRepo::default();
+        let stale = repo.create("Buy milk");
+        repo.set_expiry(&stale.id, Some(SystemTime::UNIX_EPOCH))
+            .unwrap();
+
+        assert_eq!(None, repo.find_active_by_text("Buy milk"));
+        let (fresh, created) = repo.create_unique("Buy milk");
+        assert!(created);
+        assert_ne!(stale.id, fresh.id);
+        assert_eq!(vec![fresh], repo.list(&TodoListFilter::All));
+    }
+
+    #[test]
+    fn test_create_many_skips_blank_texts() {
+        let mut repo = TodoRepo::default();
+
+        let todos = repo.create_many(vec!["Task A".into(), "  ".into(), "Task B".into()]);
+
+        assert_eq!(
+            vec!["Task A", "Task B"],
+            todos.iter().map(|t| t.text.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![1, 2],
+            todos.iter().map(|t| t.ref_num).collect::<Vec<_>>()
+        );
+        assert_eq!(2, repo.num_all_items);
+        assert_eq!(2, repo.num_active_items);
+    }
+
+    #[test]
+    fn test_cursor_pages_are_stable_under_inserts() {
+        let mut repo = TodoRepo::default();
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for i in 0..5 {
+            let mut todo = Todo::new(format!("Task {i}"));
+            todo.created_at = base + Duration::from_secs(i);
+            repo.import(vec![todo]).unwrap();
+        }
+
+        let (first, cursor) = repo.list_after(&TodoListFilter::All, None, 2);
+        repo.create("Inserted mid-way");
+        let mut seen: Vec<_> = first.into_iter().map(|t| t.text).collect();
+        let mut cursor = cursor;
+        while let Some(after) = cursor {
+            let (page, next) = repo.list_after(&TodoListFilter::All, Some(after), 2);
+            seen.extend(page.into_iter().map(|t| t.text));
+            cursor = next;
+        }
+
+        assert_eq!(vec!["Task 4", "Task 3", "Task 2", "Task 1", "Task 0"], seen);
+    }
+
+    #[test]
+    fn test_cursor_round_trips_through_string() {
+        let todo = Todo::new("Task A");
+        let cursor = Cursor::of(&todo);
+
+        assert_eq!(Ok(cursor), cursor.to_string().parse());
+        assert_eq!(
+            Err(TodoRepoError::InvalidCursor),
+            "garbage".parse::<Cursor>()
+        );
+        assert_eq!(
+            Err(TodoRepoError::InvalidCursor),
+            "12_not-a-uuid".parse::<Cursor>()
+        );
+    }
+
+    #[test]
+    fn test_reorder_moves_todo_and_shifts_others() {
+        let mut repo = TodoRepo::default();
+        for text in ["Task A", "Task B", "Task C", "Task D"] {
+            repo.create(text);
+        }
+        let manual_texts = |repo: &TodoRepo| -> Vec<String> {
+            repo.list(&TodoListFilter::Manual)
+                .into_iter()
+                .map(|t| t.text)
+                .collect()
+        };
+        assert_eq!(
+            vec!["Task A", "Task B", "Task C", "Task D"],
+            manual_texts(&repo)
+        );
+
+        let task_d = repo.get_by_ref(4).unwrap();
+        repo.reorder(&task_d.id, 1).unwrap();
+        assert_eq!(
+            vec!["Task A", "Task D", "Task B", "Task C"],
+            manual_texts(&repo)
+        );
+
+        let task_a = repo.get_by_ref(1).unwrap();
+        repo.reorder(&task_a.id, 99).unwrap();
+        assert_eq!(
+            vec!["Task D", "Task B", "Task C", "Task A"],
+            manual_texts(&repo)
+        );
+
+        repo.create("Task E");
+        assert_eq!("Task E", manual_texts(&repo)[4]);
+        assert_eq!("Task E", repo.list(&TodoListFilter::All)[0].text);
+        assert_eq!(
+            Err(TodoRepoError::NotFound),
+            repo.reorder(&Uuid::new_v4(), 0)
+        );
+    }
+
+    #[test]
+    fn test_create_with_tags_normalizes() {
+        let mut repo = TodoRepo::default();
+
+        let todo = repo.create_with_tags(
+            "Task A",
+            vec!["Work".into(), " work ".into(), "".into(), "Home".into()],
+        );
+
+        assert_eq!(vec!["work", "home"], todo.tags);
+        assert!(repo.create("Task B").tags.is_empty());
+    }
+
+    #[test]
+    fn test_set_tags_with_empty_vec_clears() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create_with_tags("Task A", vec!["work".into()]);
+
+        let todo = repo.set_tags(&todo.id, Vec::new()).unwrap();
+
+        assert!(todo.tags.is_empty());
+        assert_eq!(
+            Err(TodoRepoError::NotFound),
+            repo.set_tags(&Uuid::new_v4(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_clear_tags_on_selected_todos() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create_with_tags("Task A", vec!["work".into()]);
+        let task_b = repo.create_with_tags("Task B", vec!["home".into()]);
+        let task_c = repo.create_with_tags("Task C", vec!["work".into(), "home".into()]);
+        let unknown = Uuid::new_v4();
+
+        let missing = repo.clear_tags(&[task_a.id, unknown, task_c.id]);
+
+        assert_eq!(vec![unknown], missing);
+        assert!(repo.get(&task_a.id).unwrap().tags.is_empty());
+        assert!(repo.get(&task_c.id).unwrap().tags.is_empty());
+        assert_eq!(vec!["home"], repo.get(&task_b.id).unwrap().tags);
+    }
+
+    #[test]
+    fn test_list_by_tag() {
+        let mut repo = TodoRepo::default();
+        repo.create_with_tags("Task A", vec!["work".into()]);
+        repo.create_with_tags("Task B", vec!["home".into(), "work".into()]);
+        repo.create_with_tags("Task C", vec!["home".into()]);
+
+        let texts: Vec<_> = repo
+            .list_by_tag("WORK")
+            .into_iter()
+            .map(|t| t.text)
+            .collect();
+
+        assert_eq!(vec!["Task B", "Task A"], texts);
+        assert!(repo.list_by_tag("garden").is_empty());
+    }
+
+    #[test]
+    fn test_query_combines_filters() {
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create_with_tags("Task A", vec!["work".into()]);
+        repo.set_priority(&task_a.id, Priority::High).unwrap();
+        let task_b = repo.create_with_tags("Task B", vec!["work".into()]);
+        repo.set_priority(&task_b.id, Priority::High).unwrap();
+        repo.update(&task_b.id, None, Some(true), None).unwrap();
+        let task_c = repo.create_with_tags("Task C", vec!["home".into()]);
+        repo.set_priority(&task_c.id, Priority::High).unwrap();
+        repo.create_with_tags("Task D", vec!["work".into()]);
+
+        let query = TodoQuery {
+            priority: Some(Priority::High),
+            tag: Some("Work".into()),
+            ..TodoQuery::new(TodoListFilter::Active)
+        };
+        let todos = repo.query(&query).unwrap();
+
+        assert_eq!(
+            vec![task_a.id],
+            todos.iter().map(|t| t.id).collect::<Vec<_>>()
+        );
+        assert!(!query.is_plain());
+        assert!(TodoQuery::new(TodoListFilter::Active).is_plain());
+
+        let query = TodoQuery {
+            text: Some("task".into()),
+            pattern: Some("[".into()),
+            ..TodoQuery::new(TodoListFilter::All)
+        };
+        assert_eq!(Err(TodoRepoError::InvalidPattern), repo.query(&query));
+    }
+
+    #[test]
+    fn test_complete_and_recur() {
+        let mut repo = TodoRepo::default();
+        let chore = repo.create_with_tags("Water plants", vec!["home".into()]);
+        repo.set_recurrence(&chore.id, Some(Duration::from_secs(7 * 86_400)))
+            .unwrap();
+
+        let (done, next) = repo.complete_and_recur(&chore.id).unwrap();
+        let next = next.unwrap();
+
+        assert!(done.is_completed);
+        assert!(!next.is_completed);
+        assert_ne!(chore.id, next.id);
+        assert_eq!("Water plants", next.text);
+        assert_eq!(vec!["home"], next.tags);
+        assert_eq!(done.recurrence, next.recurrence);
+        assert_eq!(
+            Some(done.completed_at.unwrap() + Duration::from_secs(7 * 86_400)),
+            next.due_at
+        );
+        assert!(next.created_at >= chore.created_at);
+        assert_eq!(
+            (1, 1, 2),
+            (
+                repo.num_completed_items,
+                repo.num_active_items,
+                repo.num_all_items
+            )
+        );
+
+        repo.update(&chore.id, None, Some(false), None).unwrap();
+        repo.update(&next.id, None, Some(true), None).unwrap();
+        assert_eq!(3, repo.num_all_items);
+
+        let (_, again) = repo.complete_and_recur(&next.id).unwrap();
+        assert!(again.is_none());
+        assert_eq!(3, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_non_recurring_todo_does_not_recur() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let (done, next) = repo.complete_and_recur(&todo.id).unwrap();
+
+        assert!(done.is_completed);
+        assert!(next.is_none());
+        assert_eq!(1, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_update_priority() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+
+        let todo = repo.set_priority(&todo.id, Priority::Low).unwrap();
+
+        assert_eq!(Priority::Low, todo.priority);
+        assert_eq!(Priority::Low, repo.get(&todo.id).unwrap().priority);
+        assert_eq!(
+            Err(TodoRepoError::NotFound),
+            repo.set_priority(&Uuid::new_v4(), Priority::High)
+        );
+    }
+
+    #[test]
+    fn test_list_orders_same_time_by_priority() {
+        let mut repo = TodoRepo::default();
+        let created_at = SystemTime::now();
+        let earlier = {
+            let mut todo = Todo::new("Earlier");
+            todo.created_at = created_at - DAY;
+            todo.priority = Priority::High;
+            todo
+        };
+        let todos: Vec<_> = [Priority::Low, Priority::High, Priority::Medium]
+            .into_iter()
+            .map(|priority| {
+                let mut todo = Todo::new(format!("{priority:?}"));
+                todo.created_at = created_at;
+                todo.priority = priority;
+                todo
+            })
+            .chain([earlier])
+            .collect();
+        repo.import(todos).unwrap();
+
+        let texts: Vec<_> = repo
+            .list(&TodoListFilter::All)
+            .into_iter()
+            .map(|t| t.text)
+            .collect();
+
+        assert_eq!(vec!["High", "Medium", "Low", "Earlier"], texts);
+    }
+
+    #[test]
+    fn test_diff_reports_added_and_removed() {
+        let kept = Todo::new("Kept");
+        let removed = Todo::new("Removed");
+        let added = Todo::new("Added");
+
+        let result = diff(&[kept.clone(), removed.clone()], &[kept, added.clone()]);
+
+        assert_eq!(
+            RepoDiff {
+                added: vec![added.id],
+                removed: vec![removed.id],
+                changed: vec![],
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn test_update_checks_expected_version() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        assert_eq!(0, todo.version);
+
+        let todo = repo.update(&todo.id, None, Some(true), Some(0)).unwrap();
+        assert_eq!(1, todo.version);
+
+        assert_eq!(
+            Err(TodoRepoError::VersionConflict { current: 1 }),
+            repo.update(&todo.id, Some("Task B".into()), None, Some(0))
+        );
+        assert_eq!("Task A", repo.get(&todo.id).unwrap().text);
+
+        let todo = repo
+            .update(&todo.id, Some("Task B".into()), None, None)
+            .unwrap();
+        assert_eq!(2, todo.version);
+        assert_eq!(
+            Err(TodoRepoError::NotFound),
+            repo.update(&Uuid::new_v4(), None, None, Some(0))
+        );
+    }
+
+    #[test]
+    fn test_every_mutation_bumps_version_once() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        let other = repo.create("Task B");
+        let id = todo.id;
+        let now = SystemTime::now();
+        let mut expected = 0;
+        let mut check = |repo: &TodoRepo, mutation: &str| {
+            expected += 1;
+            assert_eq!(expected, repo.get(&id).unwrap().version, "{mutation}");
+        };
+
+        repo.update(&id, Some("Task A v2".into()), None, None)
+            .unwrap();
+        check(&repo, "update");
+        repo.set_progress(&id, 50).unwrap();
+        check(&repo, "set_progress");
+        repo.set_due(&id, Some(now)).unwrap();
+        check(&repo, "set_due");
+        repo.set_priority(&id, Priority::High).unwrap();
+        check(&repo, "set_priority");
+        repo.set_tags(&id, vec!["work".into()]).unwrap();
+        check(&repo, "set_tags");
+        repo.clear_tags(&[id]);
+        check(&repo, "clear_tags");
+        repo.set_metadata(&id, "key", "value").unwrap();
+        check(&repo, "set_metadata");
+        repo.delete_metadata(&id, "key").unwrap();
+        check(&repo, "delete_metadata");
+        repo.set_expiry(&id, Some(now + DAY)).unwrap();
+        check(&repo, "set_expiry");
+        repo.set_recurrence(&id, Some(DAY)).unwrap();
+        check(&repo, "set_recurrence");
+        repo.reorder(&id, 1).unwrap();
+        check(&repo, "reorder");
+        repo.dismiss(&id).unwrap();
+        check(&repo, "dismiss");
+        repo.restore(&id).unwrap();
+        check(&repo, "restore");
+        repo.toggle_completed(&TodoToggleAction::Check);
+        check(&repo, "toggle_completed");
+        repo.toggle_completed_filtered(&TodoToggleAction::Uncheck, &TodoListFilter::All);
+        check(&repo, "toggle_completed_filtered");
+        repo.set_progress(&id, MAX_PROGRESS).unwrap();
+        check(&repo, "set_progress to done");
+        repo.archive(&id).unwrap();
+        check(&repo, "archive");
+        repo.unarchive(&id).unwrap();
+        check(&repo, "unarchive");
+        repo.delete_completed();
+        check(&repo, "delete_completed");
+
+        assert_eq!(
+            Err(TodoRepoError::MetadataKeyNotFound),
+            repo.delete_metadata(&id, "key")
+        );
+        assert_eq!(expected, repo.get(&id).unwrap().version);
+        // Moved by the reorder, then toggled twice.
+        assert_eq!(3, repo.get(&other.id).unwrap().version);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mut source = TodoRepo::default();
+        let done = source.create("Buy milk, eggs");
+        source.update(&done.id, None, Some(true), None).unwrap();
+        source.create("Say \"hi\"");
+        let csv = source.export_csv();
+        assert!(csv.starts_with("id,text,is_completed,created_at\r\n"));
+
+        let mut target = TodoRepo::default();
+        assert_eq!(Ok(2), target.import_csv(&csv));
 
-// Impls
-impl TodoRepo {
-    pub fn get(&self, id: &Uuid) -> Result<Todo, TodoRepoError> {
-        self.items.get(id).ok_or(TodoRepoError::NotFound).cloned()
+        let imported = target.get(&done.id).unwrap();
+        assert_eq!("Buy milk, eggs", imported.text);
+        assert!(imported.is_completed);
+        assert_eq!(
+            format_timestamp(done.created_at),
+            format_timestamp(imported.created_at)
+        );
+        assert_eq!(
+            (1, 1, 2),
+            (
+                target.num_completed_items,
+                target.num_active_items,
+                target.num_all_items
+            )
+        );
+        assert_eq!(
+            Err(ImportError {
+                line: 2,
+                reason: "duplicate id"
+            }),
+            target.import_csv(&csv)
+        );
     }
 
-    pub fn list(&self, filter: &TodoListFilter) -> Vec<Todo> {
-        let mut todos: Vec<_> = self
-            .items
-            .values()
-            .filter(|t| match filter {
-                TodoListFilter::All => true,
-                TodoListFilter::Completed => t.is_completed,
-                TodoListFilter::Active => !t.is_completed,
+    #[test]
+    fn test_import_csv_reports_malformed_rows() {
+        let mut repo = TodoRepo::default();
+
+        assert_eq!(Ok(1), repo.import_csv(",Buy milk,,\n"));
+        assert_eq!(
+            Err(ImportError {
+                line: 3,
+                reason: "is_completed must be true or false"
+            }),
+            repo.import_csv(
+                "id,text,is_completed,created_at\n,Walk dog,false,\n,Feed cat,maybe,\n"
+            )
+        );
+        assert_eq!(
+            Err(ImportError {
+                line: 1,
+                reason: "expected id, text, is_completed and created_at"
+            }),
+            repo.import_csv("just text\n")
+        );
+        assert_eq!(
+            Err(ImportError {
+                line: 1,
+                reason: "invalid created_at"
+            }),
+            repo.import_csv(",Walk dog,false,yesterday\n")
+        );
+        assert_eq!(1, repo.num_all_items);
+    }
+
+    #[test]
+    fn test_list_in_range_is_inclusive() {
+        let mut repo = TodoRepo::default();
+        let monday = humantime::parse_rfc3339("2024-03-11T00:00:00Z").unwrap();
+        let todos: Vec<_> = (0..4)
+            .map(|day| {
+                let mut todo = Todo::new(format!("Day {day}"));
+                todo.created_at = monday + DAY * day;
+                todo
             })
-            .cloned()
             .collect();
+        repo.import(todos).unwrap();
 
-        todos.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        let texts = |todos: Vec<Todo>| -> Vec<String> {
+            let mut texts: Vec<_> = todos.into_iter().map(|t| t.text).collect();
+            texts.sort();
+            texts
+        };
 
-        todos
+        assert_eq!(
+            vec!["Day 1", "Day 2"],
+            texts(repo.list_in_range(
+                &TodoListFilter::All,
+                Some(monday + DAY),
+                Some(monday + DAY * 2)
+            ))
+        );
+        assert_eq!(
+            vec!["Day 2", "Day 3"],
+            texts(repo.list_in_range(&TodoListFilter::All, Some(monday + DAY * 2), None))
+        );
+        assert_eq!(
+            vec!["Day 0"],
+            texts(repo.list_in_range(&TodoListFilter::All, None, Some(monday)))
+        );
+        assert_eq!(
+            4,
+            repo.list_in_range(&TodoListFilter::All, None, None).len()
+        );
+        assert_eq!(
+            Err(TodoRepoError::InvalidTimestamp),
+            parse_timestamp("last week")
+        );
     }
 
-    pub fn create(&mut self, text: impl Into<String>) -> Todo {
-        let todo = Todo::new(text);
-        self.items.insert(todo.id, todo.clone());
+    #[test]
+    fn test_clear_removes_everything() {
+        let mut repo = TodoRepo::default();
+        let done = repo.create("Task A");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.create("Task B");
+        repo.create_draft("Draft");
 
-        self.num_active_items += 1;
-        self.num_all_items += 1;
+        repo.clear();
 
-        todo
+        assert!(repo.items.is_empty());
+        let dirty = repo.take_dirty();
+        assert_eq!(3, dirty.len());
+        assert!(dirty.iter().all(|c| matches!(c, TodoChange::Deleted(_))));
+        assert_eq!(
+            (0, 0, 0),
+            (
+                repo.num_completed_items,
+                repo.num_active_items,
+                repo.num_all_items
+            )
+        );
+        assert_eq!(4, repo.create("Task C").ref_num);
     }
 
-    pub fn delete(&mut self, id: &Uuid) -> Result<(), TodoRepoError> {
-        let old_todo = self.items.remove(id).ok_or(TodoRepoError::NotFound)?;
+    #[test]
+    fn test_diff_reports_field_changes() {
+        let mut repo = TodoRepo::default();
+        let renamed = repo.create("Task A");
+        let finished = repo.create("Task B");
+        let before = repo.export();
 
-        self.num_all_items -= 1;
-        if old_todo.is_completed {
-            self.num_completed_items -= 1;
-        } else {
-            self.num_active_items -= 1;
-        }
+        repo.update(&renamed.id, Some("Task A v2".into()), None, None)
+            .unwrap();
+        repo.update(&finished.id, None, Some(true), None).unwrap();
+        let result = diff(&before, &repo.export());
 
-        Ok(())
+        assert!(result.added.is_empty());
+        assert!(result.removed.is_empty());
+        assert_eq!(2, result.changed.len());
+        assert!(result
+            .changed
+            .contains(&(renamed.id, vec!["text", "updated_at", "version"])));
+        assert!(result.changed.contains(&(
+            finished.id,
+            vec![
+                "is_completed",
+                "completed_at",
+                "updated_at",
+                "progress",
+                "version"
+            ]
+        )));
     }
 
-    pub fn update(
-        &mut self,
-        id: &Uuid,
-        text: Option<String>,
-        is_completed: Option<bool>,
-    ) -> Result<Todo, TodoRepoError> {
-        let todo = self.items.get_mut(id).ok_or(TodoRepoError::NotFound)?;
-
-        if let Some(completed) = is_completed {
-            if todo.is_completed != completed {
-                todo.is_completed = completed;
-                if completed {
-                    self.num_completed_items += 1;
-                    self.num_active_items -= 1;
-                } else {
-                    self.num_completed_items -= 1;
-                    self.num_active_items += 1;
-                }
-            }
-        }
+    #[test]
+    fn test_overdue_todos() {
+        let mut repo = TodoRepo::default();
+        let now = SystemTime::now();
+        let late = repo.create("Late");
+        let later = repo.create("Later");
+        let done = repo.create("Done but late");
+        let upcoming = repo.create("Upcoming");
+        repo.create("No due date");
+        repo.set_due(&late.id, Some(now - DAY * 2)).unwrap();
+        repo.set_due(&later.id, Some(now - DAY)).unwrap();
+        repo.set_due(&done.id, Some(now - DAY)).unwrap();
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.set_due(&upcoming.id, Some(now + DAY)).unwrap();
 
-        if let Some(text) = text {
-            todo.text = text;
-        }
+        let overdue: Vec<_> = repo.overdue(now).into_iter().map(|t| t.id).collect();
+        assert_eq!(vec![late.id, later.id], overdue);
 
-        Ok(todo.clone())
+        let listed = repo.list(&TodoListFilter::Overdue);
+        assert_eq!(2, listed.len());
+        assert!(listed.iter().all(|t| t.id == late.id || t.id == later.id));
     }
 
-    pub fn delete_completed(&mut self) {
-        self.items.retain(|_, todo| !todo.is_completed);
-        self.num_all_items -= self.num_completed_items;
-        self.num_completed_items = 0;
+    #[test]
+    fn test_clear_due_date() {
+        let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        repo.set_due(&todo.id, Some(SystemTime::now() - DAY))
+            .unwrap();
+
+        let todo = repo.set_due(&todo.id, None).unwrap();
+
+        assert_eq!(None, todo.due_at);
+        assert!(repo.overdue(SystemTime::now()).is_empty());
     }
 
-    pub fn toggle_completed(&mut self, action: &TodoToggleAction) {
-        let is_completed: bool = match action {
-            TodoToggleAction::Check => {
-                self.num_active_items = 0;
-                self.num_completed_items = self.num_all_items;
-                true
-            }
-            TodoToggleAction::Uncheck => {
-                self.num_completed_items = 0;
-                self.num_active_items = self.num_all_items;
-                false
-            }
-        };
+    #[test]
+    fn test_save_and_load_json_file() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let mut repo = TodoRepo::default();
+        let done = repo.create("Task A");
+        repo.create("Task B");
+        repo.create_draft("Draft");
+        repo.update(&done.id, None, Some(true), None).unwrap();
 
-        for todo in self.items.values_mut() {
-            todo.is_completed = is_completed;
-        }
+        repo.save_to_path(&path).unwrap();
+        let loaded = TodoRepo::load_from_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(repo.items, loaded.items);
+        assert_eq!(3, loaded.last_ref_num);
+        assert_eq!(2, loaded.num_all_items);
+        assert_eq!(1, loaded.num_completed_items);
+        assert_eq!(1, loaded.num_active_items);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_todo_timestamps_serialize_as_rfc3339() {
+        let mut todo = Todo::new("Task A");
+        let due_at = humantime::parse_rfc3339("2024-03-11T09:00:00Z").unwrap();
+        todo.due_at = Some(due_at);
+        todo.set_completed(true, due_at);
+
+        let json = serde_json::to_value(&todo).unwrap();
+        assert_eq!("2024-03-11T09:00:00.000000000Z", json["due_at"]);
+        assert_eq!("2024-03-11T09:00:00.000000000Z", json["completed_at"]);
+        assert_eq!(serde_json::Value::Null, json["expires_at"]);
+        assert_eq!(todo, serde_json::from_value::<Todo>(json).unwrap());
+
+        let mut legacy = serde_json::to_value(&todo).unwrap();
+        legacy["due_at"] =
+            serde_json::json!({"secs_since_epoch": 1710147600, "nanos_since_epoch": 0});
+        legacy.as_object_mut().unwrap().remove("dismissed_at");
+        let loaded: Todo = serde_json::from_value(legacy).unwrap();
+        assert_eq!(Some(due_at), loaded.due_at);
+        assert_eq!(None, loaded.dismissed_at);
+    }
 
     #[test]
-    fn test_get_non_existing_todo() {
-        let repo = TodoRepo::default();
+    fn test_load_repairs_inconsistent_counters() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let mut repo = TodoRepo::default();
+        let done = repo.create("Task A");
+        repo.create("Task B");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.num_completed_items = 7;
+        repo.num_active_items = 0;
+        repo.num_all_items = 40;
+        repo.last_ref_num = 1;
 
-        let result_todo = repo.get(&Uuid::new_v4());
+        repo.save_to_path(&path).unwrap();
+        let loaded = TodoRepo::load_from_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
 
-        assert_eq!(result_todo, Err(TodoRepoError::NotFound));
+        assert_eq!(1, loaded.num_completed_items);
+        assert_eq!(1, loaded.num_active_items);
+        assert_eq!(2, loaded.num_all_items);
+        assert_eq!(2, loaded.last_ref_num);
     }
 
     #[test]
-    fn test_get_existing_todo() {
+    fn test_repair_reports_consistent_repo() {
         let mut repo = TodoRepo::default();
-        let todo = repo.create("Teste");
+        repo.create("Task A");
 
-        assert_eq!(Ok(todo.clone()), repo.get(&todo.id));
+        assert!(!repo.repair());
     }
 
     #[test]
-    fn test_list_repo_empty() {
-        let repo = TodoRepo::default();
-        let empty_list: Vec<Todo> = Vec::new();
+    fn test_load_rejects_invalid_json_file() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        fs::write(&path, "not json").unwrap();
 
-        let result_completed = repo.list(&TodoListFilter::Completed);
-        let result_active = repo.list(&TodoListFilter::Active);
-        let result_all = repo.list(&TodoListFilter::All);
+        let err = TodoRepo::load_from_path(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
 
-        assert_eq!(empty_list, result_completed);
-        assert_eq!(empty_list, result_active);
-        assert_eq!(empty_list, result_all);
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
     }
 
     #[test]
-    fn test_list_filled_repo_active() {
+    fn test_out_of_sync_counters_do_not_underflow() {
         let mut repo = TodoRepo::default();
-        let mut filled = vec![
-            repo.create("Task A"),
-            repo.create("Task B"),
-            repo.create("Task C"),
-        ];
+        let done = repo.create("Task A");
+        let open = repo.create("Task B");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.num_completed_items = 0;
+        repo.num_active_items = 0;
+        repo.num_all_items = 0;
 
-        filled.reverse();
+        repo.update(&done.id, None, Some(false), None).unwrap();
+        repo.update(&open.id, None, Some(true), None).unwrap();
+        assert_eq!(1, repo.num_completed_items);
+        assert_eq!(1, repo.num_active_items);
 
-        assert_eq!(filled, repo.list(&TodoListFilter::Active));
-        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Completed));
+        repo.num_all_items = 0;
+        repo.num_completed_items = 0;
+        repo.delete(&open.id).unwrap();
+        repo.delete_completed();
+        repo.toggle_completed(&TodoToggleAction::Check);
+
+        assert_eq!(1, repo.num_all_items);
+        assert_eq!(1, repo.num_completed_items);
+        assert_eq!(0, repo.num_active_items);
     }
 
     #[test]
-    fn test_list_filled_repo_complete() {
+    fn test_search_is_case_insensitive_and_filtered() {
         let mut repo = TodoRepo::default();
-        let mut filled = vec![
-            repo.create("Task A"),
-            repo.create("Task B"),
-            repo.create("Task C"),
-        ];
-        filled.reverse();
+        let report = repo.create("Write quarterly REPORT");
+        let done = repo.create("Send report to finance");
+        repo.create("Buy milk");
+        repo.update(&done.id, None, Some(true), None).unwrap();
 
-        for t in filled.iter_mut() {
-            let _ = repo.update(&t.id, None, Some(true));
-            t.is_completed = true;
-        }
+        let all: Vec<_> = repo
+            .search("Report", &TodoListFilter::All)
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        let active: Vec<_> = repo
+            .search("report", &TodoListFilter::Active)
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+
+        assert_eq!(vec![done.id, report.id], all);
+        assert_eq!(vec![report.id], active);
+    }
+
+    #[test]
+    fn test_blank_search_lists_everything() {
+        let mut repo = TodoRepo::default();
+        repo.create("Task A");
+        repo.create("Task B");
 
-        assert_eq!(filled, repo.list(&TodoListFilter::Completed));
-        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Active));
         assert_eq!(
             repo.list(&TodoListFilter::All),
-            repo.list(&TodoListFilter::Completed)
+            repo.search("   ", &TodoListFilter::All)
         );
     }
 
     #[test]
-    fn test_repo_propreties() {
+    fn test_focus_hides_completed_and_low_priority() {
         let mut repo = TodoRepo::default();
-        let mut todos = vec![repo.create("Task A"), repo.create("Task B")];
-        todos.reverse();
+        let now = SystemTime::now();
+        let low = repo.create_with_priority("Low", Some(Priority::Low));
+        let done = repo.create_with_priority("Done", Some(Priority::High));
+        let medium = repo.create("Medium");
+        let high_later = repo.create_with_priority("High later", Some(Priority::High));
+        let high_soon = repo.create_with_priority("High soon", Some(Priority::High));
+        let high_undated = repo.create_with_priority("High undated", Some(Priority::High));
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.set_due(&high_later.id, Some(now + DAY * 2)).unwrap();
+        repo.set_due(&high_soon.id, Some(now + DAY)).unwrap();
 
-        assert_eq!(todos, repo.list(&TodoListFilter::All));
-        assert_eq!(todos, repo.list(&TodoListFilter::Active));
-        assert_eq!(Vec::<Todo>::new(), repo.list(&TodoListFilter::Completed));
-        assert_eq!(2, repo.num_all_items);
-        assert_eq!(0, repo.num_completed_items);
-        assert_eq!(2, repo.num_active_items);
+        let focused: Vec<_> = repo
+            .focus(Priority::Medium)
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+
+        assert_eq!(
+            vec![high_soon.id, high_later.id, high_undated.id, medium.id],
+            focused
+        );
+        assert!(!focused.contains(&low.id));
+        assert_eq!(5, repo.focus(Priority::Low).len());
     }
 
     #[test]
-    fn test_delete_non_existing_todo() {
+    fn test_updated_at_tracks_real_changes() {
         let mut repo = TodoRepo::default();
+        let todo = repo.create("Task A");
+        assert_eq!(todo.created_at, todo.updated_at);
 
-        let delete_todo = repo.delete(&Uuid::new_v4());
-        assert_eq!(delete_todo, Err(TodoRepoError::NotFound));
+        let unchanged = repo
+            .update(&todo.id, Some("Task A".into()), Some(false), None)
+            .unwrap();
+        assert_eq!(todo.updated_at, unchanged.updated_at);
+
+        let renamed = repo
+            .update(&todo.id, Some("Task B".into()), None, None)
+            .unwrap();
+        assert!(renamed.updated_at > todo.updated_at);
+
+        let completed = repo.update(&todo.id, None, Some(true), None).unwrap();
+        assert!(completed.updated_at > renamed.updated_at);
+        assert_eq!(todo.created_at, completed.created_at);
     }
 
     #[test]
-    fn test_delete_one_todo() {
+    fn test_list_recently_updated() {
         let mut repo = TodoRepo::default();
-        let todo = repo.create("Task A");
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let task_c = repo.create("Task C");
+        repo.update(&task_a.id, None, Some(true), None).unwrap();
 
-        let result = repo.delete(&todo.id);
-        assert_eq!(result, Ok(()));
-        assert_eq!(0, repo.num_all_items);
+        let ids: Vec<_> = repo
+            .list_recently_updated(2)
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+
+        assert_eq!(vec![task_a.id, task_c.id], ids);
+        assert_eq!(3, repo.list_recently_updated(10).len());
+        assert!(!ids.contains(&task_b.id));
     }
 
     #[test]
-    fn test_update_non_existing_todo() {
+    fn test_take_dirty_returns_changed_todos_once() {
         let mut repo = TodoRepo::default();
-        let result = repo.update(&Uuid::new_v4(), Some("Task A".into()), None);
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let task_c = repo.create("Task C");
+        repo.take_dirty();
 
-        assert_eq!(result, Err(TodoRepoError::NotFound));
+        repo.update(&task_a.id, Some("Task A v2".into()), None, None)
+            .unwrap();
+        repo.set_priority(&task_c.id, Priority::High).unwrap();
+        let task_d = repo.create("Task D");
+
+        let dirty: Vec<_> = repo.take_dirty().iter().map(TodoChange::id).collect();
+
+        assert_eq!(vec![task_a.id, task_c.id, task_d.id], dirty);
+        assert!(!dirty.contains(&task_b.id));
+        assert!(repo.take_dirty().is_empty());
     }
 
     #[test]
-    fn test_update_one_existing_todo() {
+    fn test_deleted_todos_become_tombstones() {
+        let mut repo = TodoRepo {
+            hard_delete_completed: true,
+            ..TodoRepo::default()
+        };
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let task_c = repo.create("Task C");
+        repo.update(&task_b.id, None, Some(true), None).unwrap();
+        repo.take_dirty();
+
+        repo.delete(&task_a.id).unwrap();
+        repo.delete_completed();
+        let task_c = repo.set_priority(&task_c.id, Priority::High).unwrap();
+
+        let mut tombstones = vec![task_a.id, task_b.id];
+        tombstones.sort();
+        let mut expected: Vec<_> = tombstones.into_iter().map(TodoChange::Deleted).collect();
+        expected.push(TodoChange::Saved(Box::new(task_c)));
+        assert_eq!(expected, repo.take_dirty());
+        assert!(repo.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn test_persist_changes_appends_to_journal() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let journal = journal_path(&path);
         let mut repo = TodoRepo::default();
-        let old_todo = repo.create("Task A");
-        let new_todo = repo
-            .update(&old_todo.id, Some("Task AB".into()), None)
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+
+        repo.persist_changes(&path).unwrap();
+        assert!(path.exists());
+        assert!(!journal.exists());
+        let snapshot = fs::read(&path).unwrap();
+
+        repo.update(&task_a.id, Some("Task A v2".into()), None, None)
             .unwrap();
+        repo.delete(&task_b.id).unwrap();
+        let task_c = repo.create("Task C");
+        repo.persist_changes(&path).unwrap();
 
-        assert_ne!(old_todo, new_todo);
+        assert_eq!(snapshot, fs::read(&path).unwrap());
+        assert_eq!(3, fs::read_to_string(&journal).unwrap().lines().count());
+        let loaded = TodoRepo::load_from_path(&path).unwrap();
+        assert_eq!(repo.items, loaded.items);
+        assert_eq!(2, loaded.num_all_items);
+        assert_eq!(task_c.ref_num, loaded.last_ref_num);
+
+        repo.save_to_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!journal.exists());
     }
 
     #[test]
-    fn test_update_is_completed_true_existing_todo() {
+    fn test_load_skips_journal_left_behind_by_older_snapshot() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let journal = journal_path(&path);
         let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        repo.persist_changes(&path).unwrap();
+        repo.update(&task_a.id, Some("Task A v2".into()), None, None)
+            .unwrap();
+        repo.persist_changes(&path).unwrap();
+        let stale_journal = fs::read(&journal).unwrap();
 
-        let todo = repo.create("Task A");
-        let result = repo.update(&todo.id, None, Some(true)).unwrap();
+        // Crash after the new snapshot is renamed in, before the journal goes.
+        repo.update(&task_a.id, Some("Task A v3".into()), None, None)
+            .unwrap();
+        repo.save_to_path(&path).unwrap();
+        fs::write(&journal, stale_journal).unwrap();
+        let mut loaded = TodoRepo::load_from_path(&path).unwrap();
 
-        assert_eq!(1, repo.num_completed_items);
-        assert_eq!(0, repo.num_active_items);
-        assert_eq!(1, repo.num_all_items);
-        assert!(result.is_completed);
+        assert_eq!("Task A v3", loaded.get(&task_a.id).unwrap().text);
+
+        // Appends after the reload belong to the new snapshot and replay.
+        let task_b = loaded.create("Task B");
+        loaded.persist_changes(&path).unwrap();
+        let reloaded = TodoRepo::load_from_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&journal).unwrap();
+
+        assert_eq!(loaded.items, reloaded.items);
+        assert!(reloaded.get(&task_b.id).is_ok());
     }
 
     #[test]
-    fn test_update_is_completed_false_existing_todo() {
+    fn test_load_stops_at_torn_journal_line() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let journal = journal_path(&path);
         let mut repo = TodoRepo::default();
+        repo.create("Task A");
+        repo.persist_changes(&path).unwrap();
+        repo.create("Task B");
+        repo.persist_changes(&path).unwrap();
 
-        let todo = repo.create("Task A");
-        let result = repo.update(&todo.id, None, Some(true)).unwrap();
-        assert!(result.is_completed);
+        let mut file = fs::OpenOptions::new().append(true).open(&journal).unwrap();
+        file.write_all(b"{\"op\":\"saved\",\"todo\":{").unwrap();
+        let loaded = TodoRepo::load_from_path(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&journal).unwrap();
 
-        let result = repo.update(&todo.id, None, Some(false)).unwrap();
+        assert_eq!(repo.items, loaded.items);
+    }
 
-        assert_eq!(0, repo.num_completed_items);
-        assert_eq!(1, repo.num_active_items);
-        assert_eq!(1, repo.num_all_items);
-        assert!(!result.is_completed);
+    #[test]
+    fn test_to_rows_flattens_todos() {
+        let mut repo = TodoRepo::default();
+        let created_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut todo = Todo::new("Write report");
+        todo.created_at = created_at;
+        todo.updated_at = created_at;
+        todo.tags = vec!["work".into(), "urgent".into()];
+        todo.due_at = Some(created_at + DAY);
+        repo.import(vec![todo.clone()]).unwrap();
+
+        assert_eq!(
+            vec![TodoRow {
+                id: todo.id,
+                ref_num: 1,
+                text: "Write report".into(),
+                is_completed: false,
+                priority: Priority::Medium,
+                progress: 0,
+                tags: "work,urgent".into(),
+                created_at: "2023-11-14T22:13:20Z".into(),
+                updated_at: "2023-11-14T22:13:20Z".into(),
+                completed_at: None,
+                due_at: Some("2023-11-15T22:13:20Z".into()),
+            }],
+            repo.to_rows()
+        );
     }
 
     #[test]
-    fn test_delete_completed_todos() {
+    fn test_binary_snapshot_round_trip() {
         let mut repo = TodoRepo::default();
-        let task_a = repo.create("Task A");
-        let task_b = repo.create("Task B");
-        let task_c = repo.create("Task C");
+        let done = repo.create("Task A");
+        let open = repo.create("Task B");
+        repo.create_draft("Draft");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.set_metadata(&open.id, "ticket", "JIRA-1").unwrap();
+        repo.delete(&open.id).unwrap();
+        repo.create("Task C");
 
-        let _task_a = repo.update(&task_a.id, None, Some(true)).unwrap();
-        let _task_c = repo.update(&task_c.id, None, Some(true)).unwrap();
+        let restored = TodoRepo::from_binary(&repo.to_binary()).unwrap();
 
-        repo.delete_completed();
-        assert_eq!(0, repo.num_completed_items);
-        assert_eq!(1, repo.num_all_items);
-        assert_eq!(1, repo.num_active_items);
-        assert_eq!(vec![task_b.clone()], repo.list(&TodoListFilter::All));
+        assert_eq!(repo.items, restored.items);
+        assert_eq!(repo.last_ref_num, restored.last_ref_num);
+        assert_eq!(repo.num_all_items, restored.num_all_items);
+        assert_eq!(repo.num_completed_items, restored.num_completed_items);
+        assert_eq!(repo.num_active_items, restored.num_active_items);
+    }
+
+    #[test]
+    fn test_binary_snapshot_rejects_other_versions() {
+        let mut repo = TodoRepo::default();
+        repo.create("Task A");
+        let mut bytes = repo.to_binary();
+        bytes[0] = SNAPSHOT_VERSION + 1;
+
+        assert_eq!(
+            Err(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1)),
+            TodoRepo::from_binary(&bytes).map(|_| ())
+        );
+        assert_eq!(
+            Err(SnapshotError::Malformed),
+            TodoRepo::from_binary(&[]).map(|_| ())
+        );
+        assert_eq!(
+            Err(SnapshotError::Malformed),
+            TodoRepo::from_binary(&[SNAPSHOT_VERSION, 0xff]).map(|_| ())
+        );
     }
 }
@@ -0,0 +1,99 @@
+use std::io::{Read, Write};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::models::Todo;
+
+// Consts
+pub const MAX_SHARE_CODE_LEN: usize = 256 * 1024;
+pub const MAX_DECODED_SHARE_LEN: usize = 1024 * 1024;
+
+// Enums
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShareCodeError {
+    TooLarge,
+    Malformed,
+}
+
+// Functions
+pub fn encode_share_code(todos: &[Todo]) -> String {
+    let json = serde_json::to_vec(todos).expect("todos always serialize");
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(&json)
+        .expect("writing to a Vec never fails");
+    let compressed = encoder.finish().expect("writing to a Vec never fails");
+
+    URL_SAFE_NO_PAD.encode(compressed)
+}
+
+pub fn decode_share_code(code: &str) -> Result<Vec<Todo>, ShareCodeError> {
+    let code = code.trim();
+    if code.len() > MAX_SHARE_CODE_LEN {
+        return Err(ShareCodeError::TooLarge);
+    }
+
+    let compressed = URL_SAFE_NO_PAD
+        .decode(code)
+        .map_err(|_| ShareCodeError::Malformed)?;
+
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(MAX_DECODED_SHARE_LEN as u64 + 1)
+        .read_to_end(&mut json)
+        .map_err(|_| ShareCodeError::Malformed)?;
+
+    if json.len() > MAX_DECODED_SHARE_LEN {
+        return Err(ShareCodeError::TooLarge);
+    }
+
+    serde_json::from_slice(&json).map_err(|_| ShareCodeError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_code_round_trip() {
+        let mut todo = Todo::new("Task A");
+        todo.metadata.insert("ticket".into(), "JIRA-1".into());
+        let todos = vec![todo, Todo::new("Task B")];
+
+        let code = encode_share_code(&todos);
+
+        assert!(code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(Ok(todos), decode_share_code(&code));
+    }
+
+    #[test]
+    fn test_share_code_rejects_garbage() {
+        assert_eq!(
+            Err(ShareCodeError::Malformed),
+            decode_share_code("not a share code!")
+        );
+        assert_eq!(
+            Err(ShareCodeError::Malformed),
+            decode_share_code(&URL_SAFE_NO_PAD.encode(b"plain bytes"))
+        );
+    }
+
+    #[test]
+    fn test_share_code_rejects_oversized_payload() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(&vec![b' '; MAX_DECODED_SHARE_LEN + 1])
+            .unwrap();
+        let bomb = URL_SAFE_NO_PAD.encode(encoder.finish().unwrap());
+
+        assert_eq!(Err(ShareCodeError::TooLarge), decode_share_code(&bomb));
+        assert_eq!(
+            Err(ShareCodeError::TooLarge),
+            decode_share_code(&"a".repeat(MAX_SHARE_CODE_LEN + 1))
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    models::Todo,
+    repository::{TodoChange, TodoRepo},
+};
+
+// Consts
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS todos (
+    id TEXT PRIMARY KEY,
+    ref_num INTEGER NOT NULL,
+    is_completed INTEGER NOT NULL,
+    is_hidden INTEGER NOT NULL,
+    expires_at INTEGER,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+";
+
+// Structs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TodoCounts {
+    pub completed: u32,
+    pub active: u32,
+    pub all: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileTodoStore {
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct SqliteTodoStore {
+    conn: Mutex<Connection>,
+}
+
+// Traits
+/// Where the in-memory `TodoRepo` is loaded from on startup and written
+/// back to after every mutation.
+pub trait TodoStore: fmt::Debug + Send + Sync {
+    fn load(&self) -> io::Result<TodoRepo>;
+    /// Writes the repo's pending changes and clears them once they are stored.
+    fn persist(&mut self, todo_repo: &mut TodoRepo) -> io::Result<()>;
+}
+
+// Impls
+impl FileTodoStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl TodoStore for FileTodoStore {
+    fn load(&self) -> io::Result<TodoRepo> {
+        match TodoRepo::load_from_path(&self.path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(TodoRepo::default()),
+            loaded => loaded,
+        }
+    }
+
+    fn persist(&mut self, todo_repo: &mut TodoRepo) -> io::Result<()> {
+        todo_repo.persist_changes(&self.path)
+    }
+}
+
+impl SqliteTodoStore {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::with_connection(Connection::open(path).map_err(io::Error::other)?)
+    }
+
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(io::Error::other)?)
+    }
+
+    fn with_connection(conn: Connection) -> io::Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(io::Error::other)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Counts the listed todos with queries, like `TodoRepo` does over its
+    /// items: drafts, dismissed, archived and expired todos are left out.
+    pub fn counts(&self) -> io::Result<TodoCounts> {
+        let (all, completed): (u32, u32) = self
+            .conn()
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(is_completed), 0) FROM todos
+                 WHERE is_hidden = 0 AND (expires_at IS NULL OR expires_at > ?1)",
+                [unix_nanos(SystemTime::now())],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(io::Error::other)?;
+
+        Ok(TodoCounts {
+            completed,
+            active: all - completed,
+            all,
+        })
+    }
+}
+
+impl TodoStore for SqliteTodoStore {
+    fn load(&self) -> io::Result<TodoRepo> {
+        let (items, last_ref_num) = {
+            let conn = self.conn();
+            let mut statement = conn
+                .prepare("SELECT data FROM todos")
+                .map_err(io::Error::other)?;
+            let items = statement
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(io::Error::other)?
+                .map(|data| {
+                    let todo: Todo = serde_json::from_str(&data.map_err(io::Error::other)?)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                    Ok((todo.id, todo))
+                })
+                .collect::<io::Result<_>>()?;
+            let last_ref_num = conn
+                .query_row(
+                    "SELECT COALESCE(MAX(value), 0) FROM meta WHERE key = 'last_ref_num'",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(io::Error::other)?;
+
+            (items, last_ref_num)
+        };
+        let counts = self.counts()?;
+
+        let mut todo_repo = TodoRepo {
+            num_completed_items: counts.completed,
+            num_active_items: counts.active,
+            num_all_items: counts.all,
+            last_ref_num,
+            items,
+            ..TodoRepo::default()
+        };
+        todo_repo.repair();
+
+        Ok(todo_repo)
+    }
+
+    fn persist(&mut self, todo_repo: &mut TodoRepo) -> io::Result<()> {
+        let changes = todo_repo.pending_changes();
+        let conn = self
+            .conn
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let tx = conn.transaction().map_err(io::Error::other)?;
+
+        for change in &changes {
+            match change {
+                TodoChange::Saved(todo) => {
+                    let data = serde_json::to_string(todo).expect("todos always serialize");
+                    tx.execute(
+                        "INSERT OR REPLACE INTO todos
+                         (id, ref_num, is_completed, is_hidden, expires_at, data)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![
+                            todo.id.to_string(),
+                            todo.ref_num,
+                            todo.is_completed,
+                            todo.is_hidden(),
+                            todo.expires_at.map(unix_nanos),
+                            data
+                        ],
+                    )
+                    .map_err(io::Error::other)?;
+                }
+                TodoChange::Deleted(id) => {
+                    tx.execute("DELETE FROM todos WHERE id = ?1", [id.to_string()])
+                        .map_err(io::Error::other)?;
+                }
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('last_ref_num', ?1)",
+            [todo_repo.last_ref_num],
+        )
+        .map_err(io::Error::other)?;
+        tx.commit().map_err(io::Error::other)?;
+        todo_repo.dirty.clear();
+
+        Ok(())
+    }
+}
+
+// Functions
+fn unix_nanos(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn test_sqlite_store_round_trips_changes() {
+        let mut store = SqliteTodoStore::open_in_memory().unwrap();
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        let task_b = repo.create("Task B");
+        let task_c = repo.create("Task C");
+        repo.update(&task_b.id, None, Some(true), None).unwrap();
+        repo.set_tags(&task_b.id, vec!["home".into()]).unwrap();
+        store.persist(&mut repo).unwrap();
+        assert!(repo.dirty.is_empty());
+
+        repo.delete(&task_c.id).unwrap();
+        repo.update(&task_a.id, Some("Task A v2".into()), None, None)
+            .unwrap();
+        store.persist(&mut repo).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(repo.items, loaded.items);
+        assert_eq!(task_c.ref_num, loaded.last_ref_num);
+        assert_eq!(
+            (1, 1, 2),
+            (
+                loaded.num_completed_items,
+                loaded.num_active_items,
+                loaded.num_all_items
+            )
+        );
+    }
+
+    #[test]
+    fn test_sqlite_counts_skip_hidden_and_expired_todos() {
+        let mut store = SqliteTodoStore::open_in_memory().unwrap();
+        let mut repo = TodoRepo::default();
+        let done = repo.create("Done");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.create("Open");
+        repo.create_draft("Draft");
+        let expired = repo.create("Expired");
+        repo.set_expiry(&expired.id, Some(SystemTime::UNIX_EPOCH))
+            .unwrap();
+        let later = repo.create("Later");
+        repo.set_expiry(
+            &later.id,
+            Some(SystemTime::now() + Duration::from_secs(3600)),
+        )
+        .unwrap();
+        store.persist(&mut repo).unwrap();
+
+        assert_eq!(
+            TodoCounts {
+                completed: 1,
+                active: 2,
+                all: 3,
+            },
+            store.counts().unwrap()
+        );
+        assert_eq!(
+            (
+                repo.num_completed_items,
+                repo.num_active_items,
+                repo.num_all_items
+            ),
+            (1, 2, 3)
+        );
+    }
+
+    #[test]
+    fn test_file_store_round_trips_changes() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let mut store = FileTodoStore::new(&path);
+        assert!(store.load().unwrap().items.is_empty());
+
+        let mut repo = TodoRepo::default();
+        let task_a = repo.create("Task A");
+        store.persist(&mut repo).unwrap();
+        repo.delete(&task_a.id).unwrap();
+        repo.create("Task B");
+        store.persist(&mut repo).unwrap();
+
+        let loaded = store.load().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("json.journal"));
+
+        assert_eq!(repo.items, loaded.items);
+        assert_eq!(2, loaded.last_ref_num);
+    }
+}
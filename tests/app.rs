@@ -0,0 +1,2456 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use ferris_todo_app::{
+    app,
+    idempotency::IDEMPOTENCY_KEY_HEADER,
+    middleware::MutationLimit,
+    models::{Priority, Todo, TodoEvent, TodoListFilter, TodoToggleAction},
+    repository::TodoRepo,
+    run_until, spawn_expiry_sweeper, spawn_initial_load, spawn_snapshot_publisher,
+    store::{FileTodoStore, SqliteTodoStore, TodoStore},
+    AppState, AppStateBuilder, SharedSnapshot, SharedState, NUM_CHANGED_HEADER,
+};
+use futures_util::StreamExt;
+use scraper::{Html, Selector};
+use tokio::sync::RwLock;
+use tower::ServiceExt;
+
+// Helpers
+fn shared_state() -> SharedState {
+    Arc::new(RwLock::new(AppState::default()))
+}
+
+async fn send(router: Router, request: Request<Body>) -> Response {
+    router.oneshot(request).await.unwrap()
+}
+
+async fn get(shared_state: &SharedState, uri: &str) -> Response {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    send(app(shared_state.clone()), request).await
+}
+
+fn form_request(method: &str, uri: &str, body: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn json_request(method: &str, uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+async fn body_text(response: Response) -> String {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+fn select_texts(html: &str, selector: &str) -> Vec<String> {
+    let fragment = Html::parse_fragment(html);
+    let selector = Selector::parse(selector).unwrap();
+
+    fragment
+        .select(&selector)
+        .map(|e| e.text().collect::<String>().trim().to_string())
+        .collect()
+}
+
+// Tests
+#[tokio::test]
+async fn test_list_todos_with_regex() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.create("Write report");
+        repo.create("Buy milk");
+    }
+
+    let response = get(&shared_state, "/todo?filter=All&regex=%5EWrite").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let html = body_text(response).await;
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+}
+
+#[tokio::test]
+async fn test_list_todos_with_invalid_regex() {
+    let shared_state = shared_state();
+
+    let response = get(&shared_state, "/todo?filter=All&regex=%28Write").await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn test_concurrent_creates_are_not_lost() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        mutation_limit: Arc::new(MutationLimit::new(2, 100)),
+        ..AppState::default()
+    }));
+    let router = app(shared_state.clone());
+
+    let requests: Vec<_> = (0..50)
+        .map(|i| {
+            let request = form_request("POST", "/todo", &format!("text=Task+{i}"));
+            tokio::spawn(send(router.clone(), request))
+        })
+        .collect();
+
+    for request in requests {
+        assert_eq!(StatusCode::OK, request.await.unwrap().status());
+    }
+    assert_eq!(50, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_mutations_beyond_queue_are_rejected() {
+    let mutation_limit = Arc::new(MutationLimit::new(1, 1));
+    let shared_state = Arc::new(RwLock::new(AppState {
+        mutation_limit: mutation_limit.clone(),
+        ..AppState::default()
+    }));
+    let router = app(shared_state.clone());
+    let guard = shared_state.write().await;
+
+    let in_flight = tokio::spawn(send(
+        router.clone(),
+        form_request("POST", "/todo", "text=A"),
+    ));
+    while mutation_limit.in_flight() < 1 {
+        tokio::task::yield_now().await;
+    }
+    let queued = tokio::spawn(send(
+        router.clone(),
+        form_request("POST", "/todo", "text=B"),
+    ));
+    while mutation_limit.queued() < 1 {
+        tokio::task::yield_now().await;
+    }
+    let rejected = send(router.clone(), form_request("POST", "/todo", "text=C")).await;
+    drop(guard);
+
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, rejected.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(rejected).await).unwrap();
+    assert_eq!(
+        serde_json::json!({ "error": "too_many_mutations", "message": "Too many pending changes" }),
+        body
+    );
+    assert_eq!(StatusCode::OK, in_flight.await.unwrap().status());
+    assert_eq!(StatusCode::OK, queued.await.unwrap().status());
+    assert_eq!(2, shared_state.read().await.todo_repo.num_all_items);
+    assert_eq!(0, mutation_limit.in_flight());
+}
+
+#[tokio::test]
+async fn test_get_todo_by_prefix() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+    let prefix = &todo.id.simple().to_string()[..8];
+
+    let found = get(&shared_state, &format!("/todo/prefix/{prefix}")).await;
+    let missing = get(&shared_state, "/todo/prefix/zz").await;
+
+    assert_eq!(StatusCode::OK, found.status());
+    let html = body_text(found).await;
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+    assert_eq!(StatusCode::NOT_FOUND, missing.status());
+}
+
+#[tokio::test]
+async fn test_todo_metadata_routes() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+    let uri = format!("/todo/{}/meta/ticket", todo.id);
+
+    let set = send(
+        app(shared_state.clone()),
+        form_request("PUT", &uri, "value=JIRA-1"),
+    )
+    .await;
+    let read = get(&shared_state, &uri).await;
+    let deleted = send(app(shared_state.clone()), form_request("DELETE", &uri, "")).await;
+    let missing = get(&shared_state, &uri).await;
+
+    assert_eq!(StatusCode::OK, set.status());
+    assert_eq!("JIRA-1", body_text(read).await);
+    assert_eq!(StatusCode::OK, deleted.status());
+    assert_eq!(StatusCode::NOT_FOUND, missing.status());
+}
+
+#[tokio::test]
+async fn test_oversized_bodies_are_rejected() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        max_body_bytes: 1024,
+        ..AppState::default()
+    }));
+    let todo = shared_state.write().await.todo_repo.create("Buy milk");
+    let text = "a".repeat(2048);
+
+    for request in [
+        form_request("POST", "/todo", &format!("text={text}")),
+        form_request(
+            "PATCH",
+            &format!("/todo/{}", todo.id),
+            &format!("text={text}"),
+        ),
+        json_request("POST", "/api/todo", serde_json::json!({ "text": text })),
+    ] {
+        let response = send(app(shared_state.clone()), request).await;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, response.status());
+    }
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Buy+eggs"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(2, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_cors_allows_configured_origins() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        cors_origins: vec!["https://app.example.com".into()],
+        ..AppState::default()
+    }));
+
+    let preflight = send(
+        app(shared_state.clone()),
+        Request::options("/api/todo")
+            .header(header::ORIGIN, "https://app.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PATCH")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "content-type,idempotency-key,x-request-id",
+            )
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(
+        "https://app.example.com",
+        preflight.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN]
+    );
+    assert!(preflight.headers()[header::ACCESS_CONTROL_ALLOW_METHODS]
+        .to_str()
+        .unwrap()
+        .contains("PATCH"));
+    let allowed_headers = preflight.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS]
+        .to_str()
+        .unwrap();
+    for name in ["content-type", "idempotency-key", "x-request-id"] {
+        assert!(allowed_headers.contains(name), "{name} not allowed");
+    }
+
+    let other = send(
+        app(shared_state.clone()),
+        Request::get("/api/todo")
+            .header(header::ORIGIN, "https://evil.example.com")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(!other
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+    let shared_state = Arc::new(RwLock::new(AppState {
+        cors_origins: vec!["*".into()],
+        ..AppState::default()
+    }));
+    let any = send(
+        app(shared_state),
+        Request::get("/api/todo")
+            .header(header::ORIGIN, "https://evil.example.com")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!("*", any.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN]);
+}
+
+#[tokio::test]
+async fn test_cors_disabled_without_origins() {
+    let shared_state = shared_state();
+
+    let response = send(
+        app(shared_state),
+        Request::get("/api/todo")
+            .header(header::ORIGIN, "https://app.example.com")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(!response
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn test_missing_todo_error_as_json() {
+    let shared_state = shared_state();
+    let request = Request::get(format!("/todo/{}", uuid::Uuid::new_v4()))
+        .header(header::ACCEPT, "application/json")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(app(shared_state), request).await;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    assert_eq!(
+        "application/json",
+        response.headers()[header::CONTENT_TYPE].to_str().unwrap()
+    );
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(
+        serde_json::json!({ "error": "not_found", "message": "Todo not found" }),
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_errors_default_to_json_with_stable_codes() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        allow_delete: false,
+        ..AppState::default()
+    }));
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=+"),
+    )
+    .await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    assert_eq!(
+        "application/json",
+        response.headers()[header::CONTENT_TYPE].to_str().unwrap()
+    );
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!("blank_text", body["error"]);
+
+    let response = send(
+        app(shared_state.clone()),
+        Request::delete("/todo").body(Body::empty()).unwrap(),
+    )
+    .await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(
+        serde_json::json!({ "error": "delete_disabled", "message": "Deleting todos is disabled" }),
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_missing_todo_error_as_text() {
+    let shared_state = shared_state();
+    let request = Request::get(format!("/todo/{}", uuid::Uuid::new_v4()))
+        .header(header::ACCEPT, "text/html")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(app(shared_state), request).await;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    assert!(response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    assert_eq!("Todo not found", body_text(response).await);
+}
+
+#[tokio::test]
+async fn test_expiry_sweeper_removes_expired_todos() {
+    let shared_state = shared_state();
+    let (expired, kept) = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        (repo.create("Expired"), repo.create("Kept"))
+    };
+
+    let past = SystemTime::now() - Duration::from_secs(60);
+    let expires_at = past
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let response = send(
+        app(shared_state.clone()),
+        form_request(
+            "PUT",
+            &format!("/todo/{}/expiry", expired.id),
+            &format!("expires_at={expires_at}"),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let sweeper = spawn_expiry_sweeper(shared_state.clone(), Duration::from_millis(10));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    sweeper.abort();
+
+    let state = shared_state.read().await;
+    assert!(state.todo_repo.get(&expired.id).is_err());
+    assert!(state.todo_repo.get(&kept.id).is_ok());
+    assert_eq!(1, state.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_create_duplicate_text_rejected_when_unique() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        unique_text: true,
+        ..AppState::default()
+    }));
+    shared_state.write().await.todo_repo.create("Buy milk");
+
+    let duplicate = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=+buy+MILK"),
+    )
+    .await;
+    let distinct = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Buy+eggs"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::CONFLICT, duplicate.status());
+    assert_eq!(StatusCode::OK, distinct.status());
+    assert_eq!(2, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_create_text_of_cleared_todo_allowed_when_unique() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        unique_text: true,
+        ..AppState::default()
+    }));
+    {
+        let mut state = shared_state.write().await;
+        let milk = state.todo_repo.create("Buy milk");
+        state
+            .todo_repo
+            .update(&milk.id, None, Some(true), None)
+            .unwrap();
+    }
+
+    let cleared = send(
+        app(shared_state.clone()),
+        Request::delete("/todo").body(Body::empty()).unwrap(),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, cleared.status());
+
+    let recreated = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=buy+milk"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, recreated.status());
+    assert_eq!(1, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_update_into_duplicate_text_rejected_when_unique() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        unique_text: true,
+        ..AppState::default()
+    }));
+    let eggs = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.create("Buy milk");
+        repo.create("Buy eggs")
+    };
+    let uri = format!("/todo/{}", eggs.id);
+
+    let duplicate = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &uri, "text=Buy+Milk"),
+    )
+    .await;
+    let unchanged = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &uri, "text=Buy+eggs"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::CONFLICT, duplicate.status());
+    assert_eq!(StatusCode::OK, unchanged.status());
+    let repo = &shared_state.read().await.todo_repo;
+    assert_eq!("Buy eggs", repo.get(&eggs.id).unwrap().text);
+}
+
+#[tokio::test]
+async fn test_debug_repo_size() {
+    let shared_state = shared_state();
+    let empty = body_text(get(&shared_state, "/debug/size").await).await;
+    shared_state.write().await.todo_repo.create("Write report");
+
+    let filled = body_text(get(&shared_state, "/debug/size").await).await;
+
+    assert_eq!("0", empty);
+    assert!(filled.parse::<usize>().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_create_and_publish_draft() {
+    let shared_state = shared_state();
+
+    let created = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Write+report&is_draft=true"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, created.status());
+    let draft = {
+        let state = shared_state.read().await;
+        assert_eq!(0, state.todo_repo.num_all_items);
+        state.todo_repo.list(&TodoListFilter::Drafts).remove(0)
+    };
+
+    let drafts = body_text(get(&shared_state, "/todo?filter=Drafts").await).await;
+    assert_eq!(vec!["Write report"], select_texts(&drafts, ".todo-item p"));
+
+    let published = send(
+        app(shared_state.clone()),
+        form_request("POST", &format!("/todo/{}/publish", draft.id), ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, published.status());
+    let html = body_text(published).await;
+    assert_eq!(vec!["1"], select_texts(&html, "#todo-counter-active"));
+}
+
+#[tokio::test]
+async fn test_request_id_is_echoed() {
+    let shared_state = shared_state();
+    let request = Request::get("/todo?filter=All")
+        .header("x-request-id", "trace-1234")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(app(shared_state), request).await;
+
+    assert_eq!("trace-1234", response.headers()["x-request-id"]);
+}
+
+#[tokio::test]
+async fn test_request_id_is_generated() {
+    let shared_state = shared_state();
+
+    let response = get(&shared_state, "/todo?filter=All").await;
+
+    let request_id = response.headers()["x-request-id"].to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(request_id).is_ok());
+}
+
+#[tokio::test]
+async fn test_blank_text_update_keeps_text() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+    let uri = format!("/todo/{}", todo.id);
+
+    let empty = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &uri, "text="),
+    )
+    .await;
+    let whitespace = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &uri, "text=+++&is_completed=true"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, empty.status());
+    assert_eq!(StatusCode::OK, whitespace.status());
+    let todo = shared_state.read().await.todo_repo.get(&todo.id).unwrap();
+    assert_eq!("Write report", todo.text);
+    assert!(todo.is_completed);
+}
+
+#[tokio::test]
+async fn test_blank_text_update_rejected_when_not_kept() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        keep_text_on_blank_update: false,
+        ..AppState::default()
+    }));
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", todo.id), "text="),
+    )
+    .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let todo = shared_state.read().await.todo_repo.get(&todo.id).unwrap();
+    assert_eq!("Write report", todo.text);
+}
+
+#[tokio::test]
+async fn test_create_rejects_blank_and_too_long_text() {
+    let shared_state = shared_state();
+
+    let blank = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=+%09+"),
+    )
+    .await;
+    let too_long = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", &format!("text={}", "x".repeat(1001))),
+    )
+    .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, blank.status());
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, too_long.status());
+    assert_eq!(0, shared_state.read().await.todo_repo.items.len());
+}
+
+#[tokio::test]
+async fn test_toggle_defaults_to_selected_filter() {
+    let shared_state = shared_state();
+    let (done, open) = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        let open = repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        (done, open)
+    };
+    get(&shared_state, "/todo?filter=Completed").await;
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", "/todo?action=Uncheck", ""),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("1", response.headers()[NUM_CHANGED_HEADER]);
+    let repo = &shared_state.read().await.todo_repo;
+    assert!(!repo.get(&done.id).unwrap().is_completed);
+    assert!(!repo.get(&open.id).unwrap().is_completed);
+    assert_eq!(0, repo.num_completed_items);
+    assert_eq!(2, repo.num_active_items);
+}
+
+#[tokio::test]
+async fn test_scoped_toggle_only_changes_visible_todos() {
+    let shared_state = shared_state();
+    let (done, open) = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        let open = repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        (done, open)
+    };
+    get(&shared_state, "/todo?filter=Active").await;
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", "/todo?action=Uncheck&scoped=true", ""),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let repo = &shared_state.read().await.todo_repo;
+    assert!(repo.get(&done.id).unwrap().is_completed);
+    assert!(!repo.get(&open.id).unwrap().is_completed);
+    assert_eq!(1, repo.num_completed_items);
+}
+
+#[tokio::test]
+async fn test_unscoped_toggle_changes_all_todos() {
+    let shared_state = shared_state();
+    let done = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        done
+    };
+    get(&shared_state, "/todo?filter=Active").await;
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", "/todo?action=Uncheck&scoped=false", ""),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let repo = &shared_state.read().await.todo_repo;
+    assert!(!repo.get(&done.id).unwrap().is_completed);
+    assert_eq!(0, repo.num_completed_items);
+}
+
+#[tokio::test]
+async fn test_toggle_reports_number_of_changed_todos() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Write report");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.create("Buy milk");
+        repo.create("Walk dog");
+    }
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", "/todo?action=Check", ""),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("2", response.headers()[NUM_CHANGED_HEADER]);
+    assert_eq!(3, shared_state.read().await.todo_repo.num_completed_items);
+}
+
+#[tokio::test]
+async fn test_clear_all_requires_confirmation() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Write report");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.create("Buy milk");
+    }
+    shared_state.write().await.toggle_action = TodoToggleAction::Uncheck;
+
+    let response = send(
+        app(shared_state.clone()),
+        Request::delete("/todo/all").body(Body::empty()).unwrap(),
+    )
+    .await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    assert_eq!(2, shared_state.read().await.todo_repo.num_all_items);
+
+    let response = send(
+        app(shared_state.clone()),
+        Request::delete("/todo/all?confirm=true")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    let state = shared_state.read().await;
+    assert!(state.todo_repo.items.is_empty());
+    assert_eq!(0, state.todo_repo.num_all_items);
+    assert_eq!(0, state.todo_repo.num_completed_items);
+    assert_eq!(0, state.todo_repo.num_active_items);
+    assert_eq!(TodoToggleAction::Check, state.toggle_action);
+}
+
+#[tokio::test]
+async fn test_toggle_empty_repo_is_noop() {
+    let shared_state = shared_state();
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", "/todo?action=Check", ""),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let state = shared_state.read().await;
+    assert_eq!(TodoToggleAction::Check, state.toggle_action);
+    assert_eq!(0, state.todo_repo.num_all_items);
+    assert_eq!(0, state.todo_repo.num_completed_items);
+    assert_eq!(0, state.todo_repo.num_active_items);
+}
+
+#[tokio::test]
+async fn test_get_todo_by_ref() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.create("Write report");
+        repo.create("Buy milk");
+    }
+
+    let found = get(&shared_state, "/todo/ref/2").await;
+    let missing = get(&shared_state, "/todo/ref/3").await;
+
+    assert_eq!(StatusCode::OK, found.status());
+    let html = body_text(found).await;
+    assert_eq!(vec!["#2"], select_texts(&html, ".todo-ref"));
+    assert_eq!(vec!["Buy milk"], select_texts(&html, ".todo-item p"));
+    assert_eq!(StatusCode::NOT_FOUND, missing.status());
+}
+
+#[tokio::test]
+async fn test_share_code_round_trip() {
+    let source = shared_state();
+    {
+        let repo = &mut source.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+    }
+    let code = body_text(get(&source, "/todo/share-code").await).await;
+
+    let target = shared_state();
+    let response = send(
+        app(target.clone()),
+        form_request("POST", "/todo/import-code", &format!("code={code}")),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let html = body_text(response).await;
+    assert_eq!(vec!["Open", "Done"], select_texts(&html, ".todo-item p"));
+    let target_repo = &target.read().await.todo_repo;
+    assert_eq!(source.read().await.todo_repo.export(), target_repo.export());
+    assert_eq!(1, target_repo.num_completed_items);
+}
+
+#[tokio::test]
+async fn test_import_malformed_share_code() {
+    let shared_state = shared_state();
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo/import-code", "code=%21%21%21"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn test_stats_age_distribution() {
+    let shared_state = shared_state();
+    shared_state.write().await.todo_repo.create("Write report");
+
+    let response = get(&shared_state, "/todo/stats").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(
+        serde_json::json!([["<1d", 1], ["1-7d", 0], ["7-30d", 0], ["30d+", 0]]),
+        body["age_distribution"]
+    );
+    assert_eq!(24, body["completions_by_hour"].as_array().unwrap().len());
+    assert_eq!(1, body["num_all"]);
+    assert_eq!(1, body["num_active"]);
+    assert_eq!(0, body["num_completed"]);
+    assert_eq!(0.0, body["completion_percentage"]);
+    assert_eq!(1, body["created_last_24h"]);
+}
+
+#[tokio::test]
+async fn test_lint_route_lists_warnings() {
+    let shared_state = shared_state();
+    let blank = shared_state.write().await.todo_repo.create(" ");
+    shared_state.write().await.todo_repo.create("Buy milk");
+
+    let response = get(&shared_state, "/todo/_lint").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(
+        serde_json::json!([[blank.id.to_string(), ["BlankText"]]]),
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_delete_completed_resets_filter_when_enabled() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        reset_filter_on_delete_completed: true,
+        ..AppState::default()
+    }));
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+    }
+    get(&shared_state, "/todo?filter=Completed").await;
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("DELETE", "/todo", ""),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let html = body_text(response).await;
+    assert_eq!(vec!["Open"], select_texts(&html, ".todo-item p"));
+    assert_eq!(
+        TodoListFilter::All,
+        shared_state.read().await.selected_filter.get()
+    );
+}
+
+#[tokio::test]
+async fn test_delete_completed_keeps_filter_by_default() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+    }
+    get(&shared_state, "/todo?filter=Completed").await;
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("DELETE", "/todo", ""),
+    )
+    .await;
+
+    let html = body_text(response).await;
+    assert!(select_texts(&html, ".todo-item p").is_empty());
+    assert_eq!(
+        TodoListFilter::Completed,
+        shared_state.read().await.selected_filter.get()
+    );
+}
+
+#[tokio::test]
+async fn test_delete_completed_archives_unless_hard_delete() {
+    let shared_state = shared_state();
+    let done = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap()
+    };
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("DELETE", "/todo", ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    let response = get(&shared_state, "/todo?filter=Archived").await;
+    assert_eq!(
+        vec!["Done"],
+        select_texts(&body_text(response).await, ".todo-item p")
+    );
+    {
+        let repo = &shared_state.read().await.todo_repo;
+        assert!(repo.get(&done.id).unwrap().archived);
+        assert_eq!(1, repo.num_all_items);
+    }
+
+    // A second clear only sweeps visible todos, so archived ones survive it.
+    send(
+        app(shared_state.clone()),
+        form_request("DELETE", "/todo", ""),
+    )
+    .await;
+    assert!(shared_state.read().await.todo_repo.get(&done.id).is_ok());
+
+    let gone = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.hard_delete_completed = true;
+        let gone = repo.create("Gone");
+        repo.update(&gone.id, None, Some(true), None).unwrap()
+    };
+    send(
+        app(shared_state.clone()),
+        form_request("DELETE", "/todo", ""),
+    )
+    .await;
+    let repo = &shared_state.read().await.todo_repo;
+    assert!(repo.get(&gone.id).is_err());
+    assert!(repo.get(&done.id).unwrap().archived);
+}
+
+#[tokio::test]
+async fn test_burndown_route() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.create("Open");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+    }
+
+    let response = get(&shared_state, "/todo/burndown?days=3").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: Vec<(String, u32)> = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(3, body.len());
+    assert_eq!(
+        vec![0, 0, 1],
+        body.iter().map(|(_, n)| *n).collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn test_snapshot_reads_do_not_block_on_writers() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        snapshot_reads: Some(SharedSnapshot::default()),
+        ..AppState::default()
+    }));
+    let publisher = spawn_snapshot_publisher(shared_state.clone(), Duration::from_millis(10));
+
+    let writer = {
+        let shared_state = shared_state.clone();
+        tokio::spawn(async move {
+            for i in 0..20 {
+                let mut state = shared_state.write().await;
+                state.todo_repo.create(format!("Task {i}"));
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+    };
+
+    let router = app(shared_state.clone());
+    for _ in 0..20 {
+        let request = Request::get("/todo?filter=All")
+            .body(Body::empty())
+            .unwrap();
+        let response =
+            tokio::time::timeout(Duration::from_millis(100), send(router.clone(), request))
+                .await
+                .expect("snapshot reads must not wait for the write lock");
+        assert_eq!(StatusCode::OK, response.status());
+    }
+    writer.await.unwrap();
+
+    let mut seen = 0;
+    for _ in 0..50 {
+        let request = Request::get("/todo?filter=All")
+            .body(Body::empty())
+            .unwrap();
+        let html = body_text(send(router.clone(), request).await).await;
+        seen = select_texts(&html, ".todo-item p").len();
+        if seen == 20 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    publisher.abort();
+
+    assert_eq!(20, seen);
+}
+
+#[tokio::test]
+async fn test_snapshot_reads_reflect_mutations_and_hidden_filters() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        snapshot_reads: Some(SharedSnapshot::default()),
+        ..AppState::default()
+    }));
+    let draft = shared_state
+        .write()
+        .await
+        .todo_repo
+        .create_draft("Plan trip");
+    let router = app(shared_state.clone());
+
+    let created = send(
+        router.clone(),
+        form_request("POST", "/todo", "text=Buy+milk"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, created.status());
+
+    let list = |filter: &str| {
+        let request = Request::get(format!("/todo?filter={filter}"))
+            .body(Body::empty())
+            .unwrap();
+        let router = router.clone();
+        async move {
+            select_texts(
+                &body_text(send(router, request).await).await,
+                ".todo-item p",
+            )
+        }
+    };
+
+    // A held read lock would deadlock any read that reaches for the write lock.
+    let reader = shared_state.read().await;
+    assert_eq!(vec!["Buy milk"], list("All").await);
+    assert_eq!(vec![draft.text.clone()], list("Drafts").await);
+    assert_eq!(TodoListFilter::Drafts, reader.selected_filter.get());
+    drop(reader);
+
+    let id = shared_state
+        .read()
+        .await
+        .todo_repo
+        .list(&TodoListFilter::All)[0]
+        .id;
+    let dismissed = send(
+        router.clone(),
+        form_request("POST", &format!("/todo/{id}/dismiss"), ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, dismissed.status());
+
+    assert!(list("All").await.is_empty());
+    assert_eq!(vec!["Buy milk"], list("Dismissed").await);
+    assert_eq!(
+        TodoListFilter::Dismissed,
+        shared_state.read().await.selected_filter.get()
+    );
+
+    // Later mutations render against the filter the snapshot read recorded.
+    let created = send(
+        router.clone(),
+        form_request("POST", "/todo", "text=Buy+bread"),
+    )
+    .await;
+    assert!(select_texts(&body_text(created).await, ".todo-item p").is_empty());
+    assert_eq!(vec!["Buy bread"], list("All").await);
+    let created = send(
+        router.clone(),
+        form_request("POST", "/todo", "text=Buy+eggs"),
+    )
+    .await;
+    assert_eq!(
+        vec!["Buy eggs"],
+        select_texts(&body_text(created).await, ".todo-item p")
+    );
+}
+
+#[tokio::test]
+async fn test_complete_todo_by_signed_link() {
+    let shared_state = shared_state();
+    let (todo, token, expired) = {
+        let state = &mut *shared_state.write().await;
+        let todo = state.todo_repo.create("Reply to email");
+        let now = SystemTime::now();
+        let token = state
+            .completion_links
+            .mint(&todo.id, now + Duration::from_secs(60 * 60));
+        let expired = state
+            .completion_links
+            .mint(&todo.id, now - Duration::from_secs(60));
+        (todo, token, expired)
+    };
+    let uri = |token: &str| format!("/todo/{}/complete?token={token}", todo.id);
+
+    let response = get(&shared_state, &uri(&expired)).await;
+    assert_eq!(StatusCode::GONE, response.status());
+    assert!(
+        !shared_state
+            .read()
+            .await
+            .todo_repo
+            .get(&todo.id)
+            .unwrap()
+            .is_completed
+    );
+
+    let response = get(&shared_state, &uri(&token)).await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(
+        shared_state
+            .read()
+            .await
+            .todo_repo
+            .get(&todo.id)
+            .unwrap()
+            .is_completed
+    );
+
+    let response = get(&shared_state, &uri(&token)).await;
+    assert_eq!(StatusCode::GONE, response.status());
+}
+
+#[tokio::test]
+async fn test_complete_by_link_recurs_and_republishes_snapshot() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        snapshot_reads: Some(SharedSnapshot::default()),
+        ..AppState::default()
+    }));
+    let (chore, token) = {
+        let state = &mut *shared_state.write().await;
+        let chore = state.todo_repo.create("Water plants");
+        state
+            .todo_repo
+            .set_recurrence(&chore.id, Some(Duration::from_secs(3600)))
+            .unwrap();
+        let token = state
+            .completion_links
+            .mint(&chore.id, SystemTime::now() + Duration::from_secs(60 * 60));
+        state.publish_snapshot();
+        (chore, token)
+    };
+    let mut events = shared_state.read().await.events.subscribe();
+
+    let response = get(
+        &shared_state,
+        &format!("/todo/{}/complete?token={token}", chore.id),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    assert_eq!(TodoEvent::Updated(chore.id), events.recv().await.unwrap());
+    let TodoEvent::Created(next) = events.recv().await.unwrap() else {
+        panic!("expected the next occurrence to be announced");
+    };
+    let html = body_text(get(&shared_state, "/todo?filter=Active").await).await;
+    assert_eq!(vec!["Water plants"], select_texts(&html, ".todo-item p"));
+    let html = body_text(get(&shared_state, "/todo?filter=Completed").await).await;
+    assert_eq!(vec!["Water plants"], select_texts(&html, ".todo-item p"));
+    let repo = &shared_state.read().await.todo_repo;
+    assert!(repo.get(&chore.id).unwrap().is_completed);
+    assert!(!repo.get(&next).unwrap().is_completed);
+}
+
+#[tokio::test]
+async fn test_list_shows_partial_progress() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", todo.id), "progress=50"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let invalid = send(
+        app(shared_state.clone()),
+        form_request(
+            "PATCH",
+            &format!("/todo/{}", todo.id),
+            "progress=101&text=Changed",
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::BAD_REQUEST, invalid.status());
+
+    let html = body_text(get(&shared_state, "/todo?filter=All").await).await;
+    assert_eq!(vec!["50%"], select_texts(&html, ".todo-progress"));
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+}
+
+#[tokio::test]
+async fn test_api_create_update_and_list() {
+    let shared_state = shared_state();
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "POST",
+            "/api/todo",
+            serde_json::json!({"text": "Buy milk", "priority": "High"}),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::CREATED, response.status());
+    let created: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!("Buy milk", created["text"]);
+    assert_eq!("High", created["priority"]);
+    let created_at = created["created_at"].as_str().unwrap();
+    assert!(humantime::parse_rfc3339(created_at).is_ok());
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "PATCH",
+            &format!("/api/todo/{}", created["id"].as_str().unwrap()),
+            serde_json::json!({"is_completed": true}),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    let updated: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(true, updated["is_completed"]);
+    assert!(humantime::parse_rfc3339(updated["completed_at"].as_str().unwrap()).is_ok());
+
+    let response = get(&shared_state, "/api/todo?filter=Completed").await;
+    assert_eq!(StatusCode::OK, response.status());
+    let list: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(1, list["num_all_items"]);
+    assert_eq!(1, list["num_completed_items"]);
+    assert_eq!(created["id"], list["items"][0]["id"]);
+}
+
+#[tokio::test]
+async fn test_mutations_publish_events() {
+    let shared_state = shared_state();
+    let mut events = shared_state.read().await.events.subscribe();
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request("POST", "/api/todo", serde_json::json!({"text": "Buy milk"})),
+    )
+    .await;
+    let created: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    let id: uuid::Uuid = created["id"].as_str().unwrap().parse().unwrap();
+
+    send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{id}"), "is_completed=true"),
+    )
+    .await;
+    send(
+        app(shared_state.clone()),
+        Request::delete(format!("/todo/{id}"))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(TodoEvent::Created(id), events.recv().await.unwrap());
+    assert_eq!(TodoEvent::Updated(id), events.recv().await.unwrap());
+    assert_eq!(TodoEvent::Deleted(id), events.recv().await.unwrap());
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_completing_recurring_todos_publishes_next_occurrence() {
+    let shared_state = shared_state();
+    let (chore, weekly) = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let chore = repo.create("Water plants");
+        repo.set_recurrence(&chore.id, Some(Duration::from_secs(3600)))
+            .unwrap();
+        let weekly = repo.create("Take out trash");
+        repo.set_recurrence(&weekly.id, Some(Duration::from_secs(7 * 24 * 3600)))
+            .unwrap();
+        (chore, weekly)
+    };
+    let mut events = shared_state.read().await.events.subscribe();
+
+    send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", chore.id), "is_completed=true"),
+    )
+    .await;
+    assert_eq!(TodoEvent::Updated(chore.id), events.recv().await.unwrap());
+    let TodoEvent::Created(next) = events.recv().await.unwrap() else {
+        panic!("expected the next occurrence to be announced");
+    };
+    assert_eq!(
+        "Water plants",
+        shared_state.read().await.todo_repo.get(&next).unwrap().text
+    );
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", "/todo?action=Check", ""),
+    )
+    .await;
+    assert_eq!("2", response.headers()[NUM_CHANGED_HEADER]);
+    let mut created = Vec::new();
+    let last = loop {
+        match events.recv().await.unwrap() {
+            TodoEvent::Created(id) => created.push(id),
+            event => break event,
+        }
+    };
+    assert_eq!(TodoEvent::Reset, last);
+
+    let repo = &shared_state.read().await.todo_repo;
+    assert!(repo.get(&weekly.id).unwrap().is_completed);
+    assert_eq!(2, created.len());
+    assert!(created.iter().all(|id| !repo.get(id).unwrap().is_completed));
+    assert_eq!(5, repo.num_all_items);
+    assert_eq!(2, repo.num_active_items);
+}
+
+#[tokio::test]
+async fn test_event_stream_pushes_changes() {
+    let shared_state = shared_state();
+
+    let response = get(&shared_state, "/todo/events").await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        "text/event-stream",
+        response.headers()[header::CONTENT_TYPE]
+    );
+
+    let todo = shared_state.write().await.todo_repo.create("Buy milk");
+    shared_state
+        .read()
+        .await
+        .notify(TodoEvent::Created(todo.id));
+
+    let mut body = response.into_body().into_data_stream();
+    let frame = tokio::time::timeout(Duration::from_secs(1), body.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        format!("data: {{\"type\":\"created\",\"id\":\"{}\"}}\n\n", todo.id),
+        String::from_utf8(frame.to_vec()).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_api_get_todo() {
+    let shared_state = shared_state();
+    let todo = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let todo = repo.create_with_tags("Write report", vec!["work".into()]);
+        repo.set_priority(&todo.id, Priority::High).unwrap()
+    };
+
+    let response = get(&shared_state, &format!("/api/todo/{}", todo.id)).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(serde_json::to_value(&todo).unwrap(), body);
+    assert_eq!("Write report", body["text"]);
+    assert_eq!("High", body["priority"]);
+    assert_eq!(serde_json::json!(["work"]), body["tags"]);
+    assert!(humantime::parse_rfc3339(body["created_at"].as_str().unwrap()).is_ok());
+
+    let response = get(
+        &shared_state,
+        &format!("/api/todo/{}", uuid::Uuid::new_v4()),
+    )
+    .await;
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
+
+#[tokio::test]
+async fn test_update_with_stale_version_conflicts() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Buy milk");
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "PATCH",
+            &format!("/api/todo/{}", todo.id),
+            serde_json::json!({"text": "Buy oat milk", "version": 0}),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    let updated: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(1, updated["version"]);
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "PATCH",
+            &format!("/api/todo/{}", todo.id),
+            serde_json::json!({"text": "Buy soy milk", "priority": "High", "version": 0}),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+    let todo = shared_state.read().await.todo_repo.get(&todo.id).unwrap();
+    assert_eq!("Buy oat milk", todo.text);
+    assert_eq!(Priority::Medium, todo.priority);
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", todo.id), "text=Buy+soy+milk"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn test_setters_bump_version_for_concurrency_checks() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Buy milk");
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", todo.id), "progress=50"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        1,
+        shared_state
+            .read()
+            .await
+            .todo_repo
+            .get(&todo.id)
+            .unwrap()
+            .version
+    );
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request(
+            "PUT",
+            &format!("/todo/{}/due", todo.id),
+            "due_at=1700000000",
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        2,
+        shared_state
+            .read()
+            .await
+            .todo_repo
+            .get(&todo.id)
+            .unwrap()
+            .version
+    );
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "PATCH",
+            &format!("/api/todo/{}", todo.id),
+            serde_json::json!({"text": "Buy oat milk", "version": 1}),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::CONFLICT, response.status());
+}
+
+#[tokio::test]
+async fn test_api_delete_unknown_todo() {
+    let shared_state = shared_state();
+
+    let response = send(
+        app(shared_state.clone()),
+        Request::delete(format!("/api/todo/{}", uuid::Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+}
+
+#[tokio::test]
+async fn test_delete_rejected_when_disabled() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        allow_delete: false,
+        ..AppState::default()
+    }));
+    let done = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let done = repo.create("Done");
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        done
+    };
+
+    for request in [
+        Request::delete(format!("/todo/{}", done.id)),
+        Request::delete("/todo"),
+        Request::delete(format!("/api/todo/{}", done.id)),
+    ] {
+        let response = send(
+            app(shared_state.clone()),
+            request.body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+    }
+    assert_eq!(1, shared_state.read().await.todo_repo.num_all_items);
+
+    let html = body_text(get(&shared_state, "/todo?filter=All").await).await;
+    let document = Html::parse_fragment(&html);
+    let button = Selector::parse("#todo-delete-completed").unwrap();
+    let button = document.select(&button).next().unwrap();
+    assert!(button.value().attr("disabled").is_some());
+}
+
+#[tokio::test]
+async fn test_overdue_filter_lists_past_due_todos() {
+    let shared_state = shared_state();
+    let (late, open) = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        (repo.create("Late"), repo.create("Open"))
+    };
+    let yesterday = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+    let yesterday = yesterday
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request(
+            "PUT",
+            &format!("/todo/{}/due", late.id),
+            &format!("due_at={yesterday}"),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let html = body_text(get(&shared_state, "/todo?filter=Overdue").await).await;
+    assert_eq!(vec!["Late"], select_texts(&html, ".todo-item p"));
+
+    send(
+        app(shared_state.clone()),
+        Request::delete(format!("/todo/{}/due", late.id))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    let html = body_text(get(&shared_state, "/todo?filter=Overdue").await).await;
+    assert!(select_texts(&html, ".todo-item p").is_empty());
+    assert!(shared_state
+        .read()
+        .await
+        .todo_repo
+        .get(&open.id)
+        .unwrap()
+        .due_at
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_quick_add_creates_parsed_todo() {
+    let shared_state = shared_state();
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request(
+            "POST",
+            "/todo/quick-add",
+            "input=Buy+milk+%23shopping+%21high+tomorrow",
+        ),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let todos = shared_state
+        .read()
+        .await
+        .todo_repo
+        .list(&TodoListFilter::All);
+    assert_eq!(1, todos.len());
+    assert_eq!("Buy milk", todos[0].text);
+    assert_eq!(vec!["shopping"], todos[0].tags);
+    assert_eq!(Priority::High, todos[0].priority);
+    assert!(todos[0].due_at.unwrap() > SystemTime::now());
+}
+
+#[tokio::test]
+async fn test_mutations_are_saved_to_disk() {
+    let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+    let shared_state = Arc::new(RwLock::new(AppState {
+        store: Some(Box::new(FileTodoStore::new(&path))),
+        ..AppState::default()
+    }));
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Buy+milk"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let loaded = TodoRepo::load_from_path(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(1, loaded.num_all_items);
+    assert_eq!(
+        vec!["Buy milk"],
+        loaded
+            .list(&TodoListFilter::All)
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn test_mutations_round_trip_through_sqlite_store() {
+    let path = std::env::temp_dir().join(format!("todos-{}.db", uuid::Uuid::new_v4()));
+    let shared_state = AppStateBuilder::new()
+        .store(SqliteTodoStore::open(&path).unwrap())
+        .build();
+    spawn_initial_load(shared_state.clone()).await.unwrap();
+
+    for text in ["Buy+milk", "Buy+bread"] {
+        send(
+            app(shared_state.clone()),
+            form_request("POST", "/todo", &format!("text={text}")),
+        )
+        .await;
+    }
+    let milk = shared_state
+        .read()
+        .await
+        .todo_repo
+        .find_active_by_text("Buy milk");
+    let milk = milk.unwrap();
+    send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", milk.id), "is_completed=true"),
+    )
+    .await;
+    send(
+        app(shared_state.clone()),
+        form_request("DELETE", "/todo", ""),
+    )
+    .await;
+
+    let store = SqliteTodoStore::open(&path).unwrap();
+    let loaded = store.load().unwrap();
+    let counts = store.counts().unwrap();
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(shared_state.read().await.todo_repo.items, loaded.items);
+    assert_eq!((0, 1, 1), (counts.completed, counts.active, counts.all));
+    assert_eq!(2, loaded.last_ref_num);
+    assert!(loaded.get(&milk.id).unwrap().archived);
+}
+
+#[tokio::test]
+async fn test_shutdown_saves_todos() {
+    let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+    let shared_state = Arc::new(RwLock::new(AppState {
+        store: Some(Box::new(FileTodoStore::new(&path))),
+        ..AppState::default()
+    }));
+    shared_state.write().await.todo_repo.create("Buy milk");
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(run_until(listener, shared_state.clone(), async {
+        signal.await.ok();
+    }));
+    assert!(!path.exists());
+
+    shutdown.send(()).unwrap();
+    server.await.unwrap().unwrap();
+
+    let loaded = TodoRepo::load_from_path(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(1, loaded.num_all_items);
+}
+
+#[tokio::test]
+async fn test_csv_export_and_import() {
+    let source = shared_state();
+    source.write().await.todo_repo.create("Buy milk");
+
+    let response = get(&source, "/todo/export.csv").await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    let csv = body_text(response).await;
+
+    let target = shared_state();
+    let request = |body: String| {
+        Request::post("/todo/import.csv")
+            .header(header::CONTENT_TYPE, "text/csv")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let response = send(app(target.clone()), request(csv)).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(1, body["imported"]);
+    assert_eq!(1, body["num_all_items"]);
+
+    let response = send(
+        app(target.clone()),
+        request(",Walk dog,false,\n,\"Feed cat,true,\n".into()),
+    )
+    .await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!("malformed_csv", body["error"]);
+    assert_eq!(
+        "Malformed CSV on line 2: unterminated quoted field",
+        body["message"]
+    );
+    assert_eq!(1, target.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_list_with_search_query() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.create("Write report");
+        repo.create("Buy milk");
+    }
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&q=REPORT").await).await;
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&q=+").await).await;
+    assert_eq!(2, select_texts(&html, ".todo-item p").len());
+}
+
+#[tokio::test]
+async fn test_focus_mode_hides_low_priority_and_completed() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.create_with_priority("Someday", Some(Priority::Low));
+        let done = repo.create_with_priority("Shipped", Some(Priority::High));
+        repo.update(&done.id, None, Some(true), None).unwrap();
+        repo.create_with_priority("Fix outage", Some(Priority::High));
+        repo.create("Review PR");
+    }
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&focus=true").await).await;
+    assert_eq!(
+        vec!["Fix outage", "Review PR"],
+        select_texts(&html, ".todo-item p")
+    );
+
+    shared_state.write().await.focus_min_priority = Priority::High;
+    let html = body_text(get(&shared_state, "/todo?filter=All&focus=true").await).await;
+    assert_eq!(vec!["Fix outage"], select_texts(&html, ".todo-item p"));
+}
+
+#[tokio::test]
+async fn test_list_combines_filter_priority_and_tag() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let todo = repo.create_with_priority("Fix outage", Some(Priority::High));
+        repo.set_tags(&todo.id, vec!["work".into()]).unwrap();
+        let todo = repo.create_with_priority("Ship release", Some(Priority::High));
+        repo.set_tags(&todo.id, vec!["work".into()]).unwrap();
+        repo.update(&todo.id, None, Some(true), None).unwrap();
+        let todo = repo.create_with_priority("Call plumber", Some(Priority::High));
+        repo.set_tags(&todo.id, vec!["home".into()]).unwrap();
+        let todo = repo.create_with_priority("Tidy tickets", Some(Priority::Low));
+        repo.set_tags(&todo.id, vec!["work".into()]).unwrap();
+    }
+
+    let html =
+        body_text(get(&shared_state, "/todo?filter=Active&priority=High&tag=work").await).await;
+    assert_eq!(vec!["Fix outage"], select_texts(&html, ".todo-item p"));
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&tag=work&q=TI").await).await;
+    assert_eq!(vec!["Tidy tickets"], select_texts(&html, ".todo-item p"));
+}
+
+#[tokio::test]
+async fn test_list_by_creation_date_range() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        let mut old = Todo::new("Last month");
+        old.created_at = humantime::parse_rfc3339("2024-02-10T12:00:00Z").unwrap();
+        let mut recent = Todo::new("This week");
+        recent.created_at = humantime::parse_rfc3339("2024-03-12T12:00:00Z").unwrap();
+        repo.import(vec![old, recent]).unwrap();
+    }
+
+    let html = body_text(
+        get(
+            &shared_state,
+            "/todo?filter=All&created_after=2024-03-11T00:00:00Z&created_before=2024-03-17T23:59:59Z",
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(vec!["This week"], select_texts(&html, ".todo-item p"));
+
+    let html = body_text(
+        get(
+            &shared_state,
+            "/todo?filter=All&created_before=2024-03-12T12:00:00Z",
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(2, select_texts(&html, ".todo-item p").len());
+
+    let response = get(&shared_state, "/todo?filter=All&created_after=last+week").await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn test_tags_on_create_update_and_list_by_tag() {
+    let shared_state = shared_state();
+
+    send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Write+report&tags=Work,Urgent"),
+    )
+    .await;
+    send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo/quick-add", "input=Buy+milk+%23home"),
+    )
+    .await;
+
+    let html = body_text(get(&shared_state, "/todo/tag/work").await).await;
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+    let html = body_text(get(&shared_state, "/todo/tag/home").await).await;
+    assert_eq!(vec!["Buy milk"], select_texts(&html, ".todo-item p"));
+
+    let id = shared_state.read().await.todo_repo.list_by_tag("work")[0].id;
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{id}"), "tags="),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(shared_state
+        .read()
+        .await
+        .todo_repo
+        .get(&id)
+        .unwrap()
+        .tags
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_dismiss_and_restore_routes() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+
+    let dismissed = send(
+        app(shared_state.clone()),
+        form_request("POST", &format!("/todo/{}/dismiss", todo.id), ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, dismissed.status());
+    let html = body_text(dismissed).await;
+    assert_eq!(vec!["0"], select_texts(&html, "#todo-counter-completed"));
+
+    let html = body_text(get(&shared_state, "/todo?filter=All").await).await;
+    assert!(select_texts(&html, ".todo-item p").is_empty());
+    let html = body_text(get(&shared_state, "/todo?filter=Dismissed").await).await;
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+
+    let restored = send(
+        app(shared_state.clone()),
+        form_request("POST", &format!("/todo/{}/restore", todo.id), ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, restored.status());
+    let html = body_text(get(&shared_state, "/todo?filter=All").await).await;
+    assert_eq!(vec!["Write report"], select_texts(&html, ".todo-item p"));
+}
+
+#[tokio::test]
+async fn test_list_todos_is_paginated() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        for i in 0..60 {
+            repo.create(format!("Task {i}"));
+        }
+    }
+
+    let html = body_text(get(&shared_state, "/todo?filter=All").await).await;
+    assert_eq!(50, select_texts(&html, ".todo-item p").len());
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&offset=55&limit=10").await).await;
+    assert_eq!(5, select_texts(&html, ".todo-item p").len());
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&offset=100").await).await;
+    assert!(select_texts(&html, ".todo-item p").is_empty());
+
+    let html = body_text(get(&shared_state, "/todo?filter=All&limit=100000").await).await;
+    assert_eq!(60, select_texts(&html, ".todo-item p").len());
+}
+
+#[tokio::test]
+async fn test_bulk_create_todos() {
+    let shared_state = shared_state();
+    shared_state.write().await.todo_repo.create("Existing");
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "POST",
+            "/todo/bulk",
+            serde_json::json!([{"text": "Task A"}, {"text": " "}, {"text": "Task B"}]),
+        ),
+    )
+    .await;
+
+    assert_eq!(StatusCode::CREATED, response.status());
+    let created: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(3, created["num_all_items"]);
+    assert_eq!(3, created["num_active_items"]);
+    assert_eq!(2, created["items"].as_array().unwrap().len());
+    assert_eq!("Task A", created["items"][0]["text"]);
+    assert_eq!("Task B", created["items"][1]["text"]);
+}
+
+#[tokio::test]
+async fn test_bulk_create_rejects_duplicates_within_batch_when_unique() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        unique_text: true,
+        ..AppState::default()
+    }));
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "POST",
+            "/todo/bulk",
+            serde_json::json!([{"text": "Task B"}, {"text": "a"}, {"text": " A "}]),
+        ),
+    )
+    .await;
+
+    assert_eq!(StatusCode::CONFLICT, response.status());
+    assert_eq!(0, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_json_is_compact_unless_pretty_requested() {
+    let shared_state = shared_state();
+    shared_state.write().await.todo_repo.create("Buy milk");
+
+    let compact = body_text(get(&shared_state, "/api/todo").await).await;
+    assert!(!compact.contains('\n'));
+
+    let pretty = body_text(get(&shared_state, "/api/todo?pretty=true").await).await;
+    assert!(pretty.contains("\n  \"items\": ["));
+    let parsed: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+    assert_eq!("Buy milk", parsed["items"][0]["text"]);
+
+    shared_state.write().await.pretty_json = true;
+    let pretty = body_text(get(&shared_state, "/api/todo").await).await;
+    assert!(pretty.contains('\n'));
+    let compact = body_text(get(&shared_state, "/api/todo?pretty=false").await).await;
+    assert!(!compact.contains('\n'));
+}
+
+#[tokio::test]
+async fn test_text_length_limit_is_configurable() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        max_text_len: 3,
+        ..AppState::default()
+    }));
+    let todo = shared_state.write().await.todo_repo.create("Abc");
+
+    let created = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=%F0%9F%A6%80%F0%9F%A6%80%F0%9F%A6%80"),
+    )
+    .await;
+    let updated = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", todo.id), "text=Abcd"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, created.status());
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, updated.status());
+    assert_eq!(
+        "Abc",
+        shared_state
+            .read()
+            .await
+            .todo_repo
+            .get(&todo.id)
+            .unwrap()
+            .text
+    );
+}
+
+#[tokio::test]
+async fn test_text_only_edit_keeps_toggle_action_and_counters() {
+    let shared_state = shared_state();
+    let done = {
+        let mut state = shared_state.write().await;
+        let done = state.todo_repo.create("Write report");
+        state
+            .todo_repo
+            .update(&done.id, None, Some(true), None)
+            .unwrap();
+        state.todo_repo.create("Buy milk");
+        state.toggle_action = TodoToggleAction::Uncheck;
+        done
+    };
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}", done.id), "text=Write+summary"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let state = shared_state.read().await;
+    assert_eq!(TodoToggleAction::Uncheck, state.toggle_action);
+    assert_eq!(1, state.todo_repo.num_completed_items);
+    assert_eq!(1, state.todo_repo.num_active_items);
+    assert_eq!(2, state.todo_repo.num_all_items);
+    let todo = state.todo_repo.get(&done.id).unwrap();
+    assert_eq!("Write summary", todo.text);
+    assert!(todo.is_completed);
+}
+
+#[tokio::test]
+async fn test_restore_deleted_todo_route() {
+    let shared_state = shared_state();
+    let todo = shared_state.write().await.todo_repo.create("Write report");
+
+    let deleted = send(
+        app(shared_state.clone()),
+        form_request("DELETE", &format!("/todo/{}", todo.id), ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, deleted.status());
+
+    let restored = send(
+        app(shared_state.clone()),
+        form_request("POST", &format!("/todo/{}/restore", todo.id), ""),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, restored.status());
+    let html = body_text(restored).await;
+    assert_eq!(vec!["1"], select_texts(&html, "#todo-counter-all"));
+
+    let unknown = send(
+        app(shared_state.clone()),
+        form_request(
+            "POST",
+            &format!("/todo/{}/restore", uuid::Uuid::new_v4()),
+            "",
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::NOT_FOUND, unknown.status());
+}
+
+#[tokio::test]
+async fn test_tag_allowlist_rejects_unknown_tags() {
+    let shared_state = Arc::new(RwLock::new(AppState {
+        tag_allowlist: Some(["work".to_string(), "home".to_string()].into()),
+        ..AppState::default()
+    }));
+
+    let accepted = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Write+report&tags=Work,home"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, accepted.status());
+
+    let mut request = form_request("POST", "/todo", "text=Buy+milk&tags=home,garden,Misc");
+    request
+        .headers_mut()
+        .insert("accept", "application/json".parse().unwrap());
+    let rejected = send(app(shared_state.clone()), request).await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, rejected.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(rejected).await).unwrap();
+    assert_eq!("Tags are not allowed: garden, misc", body["message"]);
+
+    let id = shared_state.read().await.todo_repo.list_by_tag("work")[0].id;
+    let updated = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{id}"), "tags=garden"),
+    )
+    .await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, updated.status());
+
+    let state = shared_state.read().await;
+    assert_eq!(1, state.todo_repo.items.len());
+    assert_eq!(vec!["work", "home"], state.todo_repo.get(&id).unwrap().tags);
+}
+
+#[tokio::test]
+async fn test_api_list_todos_by_cursor() {
+    let shared_state = shared_state();
+    {
+        let repo = &mut shared_state.write().await.todo_repo;
+        for text in ["Task A", "Task B", "Task C"] {
+            repo.create(text);
+        }
+    }
+
+    let response = get(&shared_state, "/api/todo?limit=2").await;
+    let first: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(2, first["items"].as_array().unwrap().len());
+    let cursor = first["next_cursor"].as_str().unwrap();
+
+    let response = get(&shared_state, &format!("/api/todo?limit=2&after={cursor}")).await;
+    let second: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!("Task A", second["items"][0]["text"]);
+    assert!(second["next_cursor"].is_null());
+
+    let response = get(&shared_state, "/api/todo?after=nope").await;
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
+
+#[tokio::test]
+async fn test_reorder_todo_route() {
+    let shared_state = shared_state();
+    let task_c = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        repo.create("Task A");
+        repo.create("Task B");
+        repo.create("Task C")
+    };
+
+    let response = send(
+        app(shared_state.clone()),
+        form_request("PATCH", &format!("/todo/{}/order", task_c.id), "position=0"),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let html = body_text(response).await;
+    assert_eq!(
+        vec!["Task C", "Task A", "Task B"],
+        select_texts(&html, ".todo-item p")
+    );
+    let html = body_text(get(&shared_state, "/todo?filter=All").await).await;
+    assert_eq!(
+        vec!["Task C", "Task B", "Task A"],
+        select_texts(&html, ".todo-item p")
+    );
+}
+
+#[tokio::test]
+async fn test_health_reports_todo_count() {
+    let shared_state = shared_state();
+    shared_state.write().await.todo_repo.create("Buy milk");
+
+    let response = get(&shared_state, "/health").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(serde_json::json!({"status": "ok", "todos": 1}), body);
+}
+
+#[tokio::test]
+async fn test_ready_after_initial_load() {
+    let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+    let mut saved = TodoRepo::default();
+    saved.create("Buy milk");
+    saved.save_to_path(&path).unwrap();
+
+    let shared_state = Arc::new(RwLock::new(AppState {
+        store: Some(Box::new(FileTodoStore::new(&path))),
+        is_ready: false,
+        ..AppState::default()
+    }));
+    let response = get(&shared_state, "/ready").await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+
+    spawn_initial_load(shared_state.clone()).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let response = get(&shared_state, "/ready").await;
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(1, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_writes_rejected_until_initial_load() {
+    let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+    let mut saved = TodoRepo::default();
+    saved.create("Buy milk");
+    saved.save_to_path(&path).unwrap();
+
+    let shared_state = Arc::new(RwLock::new(AppState {
+        store: Some(Box::new(FileTodoStore::new(&path))),
+        is_ready: false,
+        ..AppState::default()
+    }));
+
+    let early = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Buy+eggs"),
+    )
+    .await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, early.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(early).await).unwrap();
+    assert_eq!(
+        serde_json::json!({ "error": "not_ready", "message": "Todos are still loading" }),
+        body
+    );
+    let early = send(
+        app(shared_state.clone()),
+        Request::post("/todo")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "text/html")
+            .body(Body::from("text=Buy+eggs"))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, early.status());
+    assert_eq!("Todos are still loading", body_text(early).await);
+    assert_eq!(
+        StatusCode::OK,
+        get(&shared_state, "/todo?filter=All").await.status()
+    );
+    assert_eq!(0, shared_state.read().await.todo_repo.num_all_items);
+
+    spawn_initial_load(shared_state.clone()).await.unwrap();
+    let late = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Buy+bread"),
+    )
+    .await;
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(StatusCode::OK, late.status());
+    let texts: Vec<_> = shared_state
+        .read()
+        .await
+        .todo_repo
+        .list(&TodoListFilter::All)
+        .into_iter()
+        .map(|t| t.text)
+        .collect();
+    assert_eq!(vec!["Buy bread", "Buy milk"], texts);
+}
+
+#[tokio::test]
+async fn test_bulk_clear_tags() {
+    let shared_state = shared_state();
+    let (cleared, kept) = {
+        let repo = &mut shared_state.write().await.todo_repo;
+        (
+            repo.create_with_tags("Write report", vec!["work".into()]),
+            repo.create_with_tags("Buy milk", vec!["home".into()]),
+        )
+    };
+    let unknown = uuid::Uuid::new_v4();
+
+    let response = send(
+        app(shared_state.clone()),
+        json_request(
+            "PATCH",
+            "/todo/bulk/clear-tags",
+            serde_json::json!({"ids": [cleared.id, unknown]}),
+        ),
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(serde_json::json!({"missing": [unknown]}), body);
+    let state = shared_state.read().await;
+    assert!(state.todo_repo.get(&cleared.id).unwrap().tags.is_empty());
+    assert_eq!(vec!["home"], state.todo_repo.get(&kept.id).unwrap().tags);
+}
+
+#[tokio::test]
+async fn test_create_with_dedupe_reuses_active_todo() {
+    let shared_state = shared_state();
+    let milk = shared_state.write().await.todo_repo.create("Buy milk");
+
+    let deduped = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo?dedupe=true", "text=+buy+MILK"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, deduped.status());
+    assert!(body_text(deduped).await.contains(&milk.id.to_string()));
+    assert_eq!(1, shared_state.read().await.todo_repo.num_all_items);
+
+    let deduped = send(
+        app(shared_state.clone()),
+        json_request(
+            "POST",
+            "/api/todo?dedupe=true",
+            serde_json::json!({ "text": "Buy milk" }),
+        ),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, deduped.status());
+    let body: serde_json::Value = serde_json::from_str(&body_text(deduped).await).unwrap();
+    assert_eq!(serde_json::json!(milk.id), body["id"]);
+
+    let fresh = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo?dedupe=true", "text=Buy+bread&priority=High"),
+    )
+    .await;
+    assert_eq!(StatusCode::CREATED, fresh.status());
+    let state = shared_state.read().await;
+    let bread = state.todo_repo.find_active_by_text("buy bread").unwrap();
+    assert_eq!(Priority::High, bread.priority);
+    assert_eq!(2, state.todo_repo.num_all_items);
+    drop(state);
+
+    let duplicated = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=buy+milk"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, duplicated.status());
+    assert_eq!(3, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_create_with_idempotency_key_replays_original_todo() {
+    let shared_state = shared_state();
+    let keyed_request = |text: &str| {
+        let mut request = form_request("POST", "/todo", &format!("text={text}"));
+        request.headers_mut().insert(
+            IDEMPOTENCY_KEY_HEADER,
+            header::HeaderValue::from_static("retry-1"),
+        );
+        request
+    };
+
+    let first = send(app(shared_state.clone()), keyed_request("Buy+milk")).await;
+    assert_eq!(StatusCode::OK, first.status());
+    let retried = send(app(shared_state.clone()), keyed_request("Buy+milk")).await;
+    assert_eq!(StatusCode::OK, retried.status());
+
+    let state = shared_state.read().await;
+    assert_eq!(1, state.todo_repo.num_all_items);
+    assert_eq!(1, state.todo_repo.list(&TodoListFilter::All).len());
+    drop(state);
+
+    let unkeyed = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Buy+milk"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, unkeyed.status());
+    assert_eq!(2, shared_state.read().await.todo_repo.num_all_items);
+}
+
+#[tokio::test]
+async fn test_builder_configures_embedded_state() {
+    let shared_state = AppStateBuilder::new()
+        .filter(TodoListFilter::Active)
+        .max_text_len(8)
+        .allow_delete(false)
+        .tag_allowlist(["Work"])
+        .build();
+    {
+        let state = shared_state.read().await;
+        assert_eq!(TodoListFilter::Active, state.selected_filter.get());
+        assert_eq!(Some(["work".to_string()].into()), state.tag_allowlist);
+        assert!(state.store.is_none());
+    }
+
+    let too_long = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Write+report"),
+    )
+    .await;
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, too_long.status());
+
+    let created = send(
+        app(shared_state.clone()),
+        form_request("POST", "/todo", "text=Report&tags=work"),
+    )
+    .await;
+    assert_eq!(StatusCode::OK, created.status());
+    assert_eq!(1, shared_state.read().await.todo_repo.num_all_items);
+
+    let state = AppStateBuilder::new()
+        .persistence_path("todos.bin")
+        .build_state();
+    assert!(format!("{:?}", state.store).contains("todos.bin"));
+    assert_eq!(AppState::default().max_text_len, state.max_text_len);
+}